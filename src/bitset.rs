@@ -0,0 +1,584 @@
+use std::time::Duration;
+
+use rand::prelude::*;
+
+use crate::branches::BranchTable;
+use crate::monitor::{Counts, Monitor, StopReason};
+use crate::profile::Profile;
+use crate::tree::{NodeKind, SearchTree};
+use crate::{DancingLinks, Statistics};
+
+/// Largest number of primary items the bitset backend can handle.
+pub const MAX_PRIMARY_ITEMS: usize = 128;
+
+// Largest item-by-option table, in 64-bit words, picked automatically.
+const MAX_AUTO_WORDS: usize = 1 << 22;
+
+/// Tells whether the bitset backend should be preferred for `dlx`, that is,
+/// if it has few primary items and its tables stay small.
+pub fn is_suitable(dlx: &DancingLinks) -> bool {
+    let words = dlx.get_option_count().div_ceil(64).max(1);
+
+    dlx.get_primary() <= MAX_PRIMARY_ITEMS && (dlx.get_item_count() + 1) * words <= MAX_AUTO_WORDS
+}
+
+/// Search backend for instances with at most `MAX_PRIMARY_ITEMS` primary
+/// items. The uncovered primary items fit in a `u128` and the active options
+/// are a bitset, so covering an item is a handful of word operations instead
+/// of walking `node_list`.
+///
+/// It explores the same tree in the same order as `DancingLinks::dance`, so
+/// solutions, counts and statistics are identical.
+pub struct BitsetDancer<'a> {
+    dlx: &'a DancingLinks,
+    words: usize,
+    item_options: Vec<u64>,
+    option_items: Vec<Vec<usize>>,
+    option_costs: Vec<usize>,
+    uniform_cost: Option<usize>,
+    option_masks: Vec<u128>,
+    option_nodes: Vec<usize>,
+}
+
+impl<'a> BitsetDancer<'a> {
+    /// Returns `None` if the instance has too many primary items.
+    pub fn new(dlx: &'a DancingLinks) -> Option<Self> {
+        if dlx.get_primary() > MAX_PRIMARY_ITEMS {
+            return None;
+        }
+
+        let option_items = dlx.get_option_items();
+        let words = option_items.len().div_ceil(64).max(1);
+
+        let mut item_options = vec![0; (dlx.get_item_count() + 1) * words];
+        let mut option_masks = vec![0; option_items.len()];
+
+        // Options left out of the instance are never linked to their items.
+        for (o, items) in option_items.iter().enumerate() {
+            if !dlx.is_option_active(o + 1) || items.iter().any(|&i| !dlx.is_item_active(i)) {
+                continue;
+            }
+
+            for &i in items {
+                item_options[i * words + o / 64] |= 1 << (o % 64);
+
+                if i <= dlx.get_primary() {
+                    option_masks[o] |= 1 << (i - 1);
+                }
+            }
+        }
+
+        let option_costs: Vec<usize> = option_items.iter().map(|items| items.len() - 1).collect();
+
+        let uniform_cost = match option_costs.first() {
+            Some(&c) if option_costs.iter().all(|&d| d == c) => Some(c),
+            _ => None,
+        };
+
+        Some(BitsetDancer {
+            dlx,
+            words,
+            item_options,
+            option_items,
+            option_costs,
+            uniform_cost,
+            option_masks,
+            option_nodes: dlx.get_option_nodes(),
+        })
+    }
+
+    pub fn dance(&self) -> Statistics {
+        self.dance_with(&[])
+    }
+
+    /// Solves the instance with the given options, numbered from 1 in input
+    /// order, forced into every solution, as `DancingLinks::dance_with` does.
+    /// Clashing assumptions give no solutions.
+    pub fn dance_with(&self, assumptions: &[usize]) -> Statistics {
+        self.search(assumptions, None)
+    }
+
+    /// Solves the instance as `dance_with` does, recording the search tree
+    /// into `tree` as it goes.
+    pub fn dance_with_tree(&self, assumptions: &[usize], tree: &mut SearchTree) -> Statistics {
+        self.search(assumptions, Some(tree))
+    }
+
+    fn search(&self, assumptions: &[usize], mut tree: Option<&mut SearchTree>) -> Statistics {
+        let config = &self.dlx.config;
+        let mut monitor = Monitor::new(config);
+
+        let words = self.words;
+        let depth = self.dlx.get_primary() + 1;
+
+        // Per level: active options and uncovered primary items on entry, the
+        // branching item, its active options and the one being tried.
+        let mut active = vec![0; (depth + 1) * words];
+        let mut uncovered: Vec<u128> = vec![0; depth + 1];
+        let mut branch = vec![0; depth * words];
+        let mut item = vec![0; depth];
+        let mut chosen = vec![0; depth];
+        let mut position = vec![0; depth];
+        let mut length = vec![0; depth];
+
+        for o in 0..self.option_items.len() {
+            active[o / 64] |= 1 << (o % 64);
+        }
+
+        for i in 1..=self.dlx.get_primary() {
+            if self.dlx.is_item_active(i) {
+                uncovered[0] |= 1 << (i - 1);
+            }
+        }
+
+        // The options of items covered by assumptions are left out from the
+        // start, like those of inactive items.
+        let mut covered: Vec<bool> = (0..=self.dlx.get_item_count())
+            .map(|i| !self.dlx.is_item_active(i))
+            .collect();
+        let mut forced = Vec::with_capacity(assumptions.len());
+
+        for &k in assumptions {
+            if k == 0 || k > self.option_items.len() {
+                panic!("Assumptions must be options of the instance.");
+            }
+
+            let items = &self.option_items[k - 1];
+
+            if !self.dlx.is_option_active(k) || items.iter().any(|&i| covered[i]) {
+                return Statistics::finished(Counts::default(), Duration::ZERO);
+            }
+
+            for &i in items {
+                covered[i] = true;
+
+                for (word, options) in active.iter_mut().zip(&self.item_options[i * words..]) {
+                    *word &= !options;
+                }
+            }
+
+            uncovered[0] &= !self.option_masks[k - 1];
+            forced.push(self.option_nodes[k - 1]);
+        }
+
+        let forced = self.dlx.with_positions(&forced);
+
+        let mut level = 0;
+        let mut enter = true;
+
+        let mut rng = match config.get_randomization_seed() {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_os_rng(),
+        };
+
+        let mut counts = Counts::default();
+
+        let mut profile = config
+            .is_profiling()
+            .then(|| Profile::new(&self.dlx.item_names));
+
+        let mut branch_table = config.get_branch_depth().map(BranchTable::new);
+
+        let show_first = config.show_first();
+        let solution_interval = config.get_solution_interval();
+        let solution_limit = config.get_solution_limit();
+        let mut printed_solutions = 0;
+
+        let randomize = config.get_randomization_seed().is_some();
+
+        // The deepest partial solution so far that is not a solution, shown if the
+        // search is stopped.
+        let mut deepest = forced.clone();
+        let mut deepest_uncovered = get_items(uncovered[0]);
+
+        loop {
+            let stop = monitor.check(
+                &counts,
+                None,
+                enter,
+                || {
+                    if enter {
+                        uncovered[level] != 0
+                    } else {
+                        self.next_option(
+                            &branch[level * words..(level + 1) * words],
+                            chosen[level],
+                            position[level],
+                        )
+                        .is_some()
+                    }
+                },
+                || (0..level).map(|l| (Some(position[l]), length[l])).collect(),
+            );
+
+            if let Some(stop) = stop {
+                self.dlx.print_partial_at(
+                    &self.get_solution(&forced, &chosen[..level], &item, &position, &length),
+                    stop.explored,
+                    &deepest,
+                    &deepest_uncovered,
+                );
+
+                if let Some(table) = &mut branch_table {
+                    table.finish(0, counts.solutions, counts.nodes, monitor.elapsed());
+                }
+
+                return Statistics {
+                    profile,
+                    branches: branch_table,
+                    ..Statistics::stopped(counts, monitor.elapsed(), stop.reason)
+                };
+            }
+
+            if enter {
+                enter = false;
+                counts.nodes += 1;
+
+                if uncovered[level] == 0 {
+                    if counts.max_level < level + 1 {
+                        counts.max_level = level + 1;
+                    }
+
+                    counts.solutions += 1;
+
+                    if let Some(profile) = &mut profile {
+                        profile.add_solution(level);
+                    }
+
+                    if let Some(tree) = tree.as_deref_mut() {
+                        tree.add_node(
+                            level,
+                            || NodeKind::Solution,
+                            || {
+                                let l = level - 1;
+
+                                self.get_tree_option(chosen[l], item[l], position[l], length[l])
+                            },
+                        );
+                    }
+
+                    if (show_first && counts.solutions == 1)
+                        || (solution_interval > 0 && counts.solutions % solution_interval == 0)
+                    {
+                        self.dlx.print_solution_at(
+                            counts.solutions,
+                            &self.get_solution(
+                                &forced,
+                                &chosen[..level],
+                                &item,
+                                &position,
+                                &length,
+                            ),
+                            printed_solutions == 0,
+                        );
+
+                        printed_solutions += 1;
+                    }
+
+                    if solution_limit == Some(counts.solutions) {
+                        if let Some(table) = &mut branch_table {
+                            table.finish(0, counts.solutions, counts.nodes, monitor.elapsed());
+                        }
+
+                        return Statistics {
+                            profile,
+                            branches: branch_table,
+                            ..Statistics::stopped(
+                                counts,
+                                monitor.elapsed(),
+                                StopReason::LimitReached,
+                            )
+                        };
+                    }
+
+                    if level == 0 {
+                        break;
+                    }
+
+                    level -= 1;
+
+                    continue;
+                }
+
+                let a = &active[level * words..(level + 1) * words];
+
+                let mut min_length = usize::MAX;
+                let mut i = 0;
+
+                let mut p = uncovered[level];
+
+                while p != 0 {
+                    let j = p.trailing_zeros() as usize + 1;
+                    let length = self.count_active(a, j, min_length);
+
+                    if length < min_length {
+                        min_length = length;
+                        i = j;
+
+                        if min_length == 0 {
+                            break;
+                        }
+                    }
+
+                    p &= p - 1;
+                }
+
+                if randomize {
+                    let mut candidates = 0;
+
+                    p = uncovered[level];
+
+                    while p != 0 {
+                        let j = p.trailing_zeros() as usize + 1;
+
+                        if self.count_active(a, j, min_length + 1) == min_length {
+                            candidates += 1;
+
+                            if rng.random_range(0..candidates) == 0 {
+                                i = j;
+                            }
+                        }
+
+                        p &= p - 1;
+                    }
+                }
+
+                if counts.max_degree < min_length {
+                    counts.max_degree = min_length;
+                }
+
+                if let Some(profile) = &mut profile {
+                    profile.add_branch(level, i, min_length);
+                }
+
+                if let Some(tree) = tree.as_deref_mut() {
+                    tree.add_node(
+                        level,
+                        || NodeKind::Branch {
+                            item: self.dlx.item_names[i].clone(),
+                            degree: min_length,
+                        },
+                        || {
+                            let l = level - 1;
+
+                            self.get_tree_option(chosen[l], item[l], position[l], length[l])
+                        },
+                    );
+                }
+
+                let b = &mut branch[level * words..(level + 1) * words];
+
+                counts.updates += 1;
+
+                for (w, word) in b.iter_mut().enumerate() {
+                    *word = a[w] & self.item_options[i * words + w];
+                    counts.updates += self.hide_cost(*word, w);
+                }
+
+                item[level] = i;
+                chosen[level] = 0;
+                position[level] = 0;
+                length[level] = min_length;
+            }
+
+            let Some(o) = self.next_option(
+                &branch[level * words..(level + 1) * words],
+                chosen[level],
+                position[level],
+            ) else {
+                if let Some(table) = &mut branch_table {
+                    table.finish(level, counts.solutions, counts.nodes, monitor.elapsed());
+                }
+
+                if level == 0 {
+                    break;
+                }
+
+                level -= 1;
+
+                continue;
+            };
+
+            chosen[level] = o;
+            position[level] += 1;
+
+            let i = item[level];
+
+            if let Some(table) = &mut branch_table
+                && level < table.get_depth()
+            {
+                table.start(
+                    level,
+                    self.get_tree_option(o, i, position[level], length[level]),
+                    counts.solutions,
+                    counts.nodes,
+                    monitor.elapsed(),
+                );
+            }
+
+            let (a, next) = active.split_at_mut((level + 1) * words);
+            let a = &a[level * words..];
+            let next = &mut next[..words];
+
+            for (w, word) in next.iter_mut().enumerate() {
+                *word = a[w] & !self.item_options[i * words + w];
+            }
+
+            for &j in &self.option_items[o] {
+                if j == i {
+                    continue;
+                }
+
+                counts.updates += 1;
+
+                for (w, word) in next.iter_mut().enumerate() {
+                    let removed = *word & self.item_options[j * words + w];
+
+                    counts.updates += self.hide_cost(removed, w);
+                    *word &= !removed;
+                }
+            }
+
+            uncovered[level + 1] = uncovered[level] & !self.option_masks[o];
+
+            level += 1;
+            enter = true;
+
+            if counts.max_level < level {
+                counts.max_level = level;
+            }
+
+            if uncovered[level] != 0 && deepest.len() < forced.len() + level {
+                deepest = self.get_solution(&forced, &chosen[..level], &item, &position, &length);
+                deepest_uncovered = get_items(uncovered[level]);
+            }
+        }
+
+        if let Some(table) = &mut branch_table {
+            table.finish(0, counts.solutions, counts.nodes, monitor.elapsed());
+        }
+
+        Statistics {
+            profile,
+            branches: branch_table,
+            ..Statistics::finished(counts, monitor.elapsed())
+        }
+    }
+
+    // Pairs the options chosen at each level with their nodes in the branching
+    // items, after the forced options, as `DancingLinks::print_solution_at`
+    // expects.
+    fn get_solution(
+        &self,
+        forced: &[(usize, Option<usize>, usize)],
+        chosen: &[usize],
+        item: &[usize],
+        position: &[usize],
+        length: &[usize],
+    ) -> Vec<(usize, Option<usize>, usize)> {
+        let options = (0..chosen.len()).map(|l| {
+            let x = self.get_node(chosen[l], item[l]);
+
+            (x, Some(position[l]), length[l])
+        });
+
+        forced.iter().copied().chain(options).collect()
+    }
+
+    // Gives the number and text of option `o`, chosen for item `i`, as an
+    // edge of the search tree or a branch counted by `--branch-counts`.
+    fn get_tree_option(
+        &self,
+        o: usize,
+        i: usize,
+        position: usize,
+        length: usize,
+    ) -> (usize, String) {
+        let option = self
+            .dlx
+            .get_chosen_option(self.get_node(o, i), Some(position), length);
+
+        (option.option, option.to_text())
+    }
+
+    // Counts active options of item `i`, giving up once `limit` is reached.
+    fn count_active(&self, active: &[u64], i: usize, limit: usize) -> usize {
+        let options = &self.item_options[i * self.words..(i + 1) * self.words];
+
+        let mut count = 0;
+
+        for (a, o) in active.iter().zip(options) {
+            count += (a & o).count_ones() as usize;
+
+            if count >= limit {
+                break;
+            }
+        }
+
+        count
+    }
+
+    // Updates done by `hide` on every option in a word of the bitset.
+    fn hide_cost(&self, mut word: u64, w: usize) -> usize {
+        if let Some(c) = self.uniform_cost {
+            return c * word.count_ones() as usize;
+        }
+
+        let mut cost = 0;
+
+        while word != 0 {
+            let o = 64 * w + word.trailing_zeros() as usize;
+
+            cost += self.option_costs[o];
+            word &= word - 1;
+        }
+
+        cost
+    }
+
+    // Finds the option after `current` in `branch`, or the first one if no
+    // option has been tried yet.
+    fn next_option(&self, branch: &[u64], current: usize, tried: usize) -> Option<usize> {
+        let start = if tried == 0 { 0 } else { current + 1 };
+
+        let mut w = start / 64;
+
+        if w >= self.words {
+            return None;
+        }
+
+        let mut word = branch[w] & (u64::MAX << (start % 64));
+
+        loop {
+            if word != 0 {
+                return Some(64 * w + word.trailing_zeros() as usize);
+            }
+
+            w += 1;
+
+            if w == self.words {
+                return None;
+            }
+
+            word = branch[w];
+        }
+    }
+
+    fn get_node(&self, o: usize, i: usize) -> usize {
+        let k = self.option_items[o].iter().position(|&j| j == i).unwrap();
+
+        self.option_nodes[o] + k
+    }
+}
+
+// Lists the primary items in mask `p`.
+fn get_items(mut p: u128) -> Vec<usize> {
+    let mut items = Vec::new();
+
+    while p != 0 {
+        items.push(p.trailing_zeros() as usize + 1);
+        p &= p - 1;
+    }
+
+    items
+}
@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+/// The subtree under one option tried at the top levels of the search.
+pub struct Branch {
+    pub level: usize,
+    /// Index of the branch at the level above it, if any.
+    pub parent: Option<usize>,
+    pub option: usize,
+    pub option_text: String,
+    pub solutions: usize,
+    pub nodes: usize,
+    pub elapsed_time: Duration,
+}
+
+/// Solutions, nodes and time under every option tried at the first levels,
+/// gathered with `--branch-counts`. Branches are kept in the order they are
+/// tried, so that the ones under a branch follow it.
+pub struct BranchTable {
+    pub branches: Vec<Branch>,
+    depth: usize,
+    // The branch being explored at each level, and the counts when it began.
+    open: Vec<(usize, usize, usize, Duration)>,
+}
+
+impl BranchTable {
+    pub(crate) fn new(depth: usize) -> Self {
+        BranchTable {
+            branches: Vec::new(),
+            depth,
+            open: Vec::new(),
+        }
+    }
+
+    pub(crate) fn get_depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Starts the branch of `option`, tried at `level`, which ends the
+    /// branches of the options tried before it at that level or below.
+    pub(crate) fn start(
+        &mut self,
+        level: usize,
+        option: (usize, String),
+        solutions: usize,
+        nodes: usize,
+        elapsed_time: Duration,
+    ) {
+        self.finish(level, solutions, nodes, elapsed_time);
+
+        let parent = level.checked_sub(1).map(|l| self.open[l].0);
+
+        self.open
+            .push((self.branches.len(), solutions, nodes, elapsed_time));
+
+        self.branches.push(Branch {
+            level,
+            parent,
+            option: option.0,
+            option_text: option.1,
+            solutions: 0,
+            nodes: 0,
+            elapsed_time: Duration::ZERO,
+        });
+    }
+
+    /// Ends the branches open at `level` and below, given the counts so far.
+    pub(crate) fn finish(
+        &mut self,
+        level: usize,
+        solutions: usize,
+        nodes: usize,
+        elapsed_time: Duration,
+    ) {
+        while self.open.len() > level {
+            let (k, start_solutions, start_nodes, start_time) = self.open.pop().unwrap();
+            let branch = &mut self.branches[k];
+
+            branch.solutions = solutions - start_solutions;
+            branch.nodes = nodes - start_nodes;
+            branch.elapsed_time = elapsed_time - start_time;
+        }
+    }
+}
@@ -0,0 +1,1060 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use rand::prelude::*;
+
+use crate::branches::BranchTable;
+use crate::config::Config;
+use crate::monitor::{Counts, Monitor, StopReason};
+use crate::output::{self, ChosenOption};
+use crate::profile::Profile;
+use crate::tree::{NodeKind, SearchTree};
+use crate::utils::{self, FnvBuildHasher, OptionData};
+use crate::{DancingLinks, Statistics};
+
+/// Exact cover solver based on Knuth's dancing cells, with the same public
+/// API as `DancingLinks`.
+///
+/// Instead of doubly linked lists, the active options of every item and the
+/// active items are kept as sparse sets: a prefix of an array plus the
+/// position of every element in it. Removing an element swaps it with the
+/// last active one and shrinks the prefix, and since removals are undone in
+/// reverse order, restoring it only grows the prefix back.
+///
+/// Swaps reorder the sets, so ties between items and the order of the options
+/// tried differ from `DancingLinks`. The solutions are the same, but they are
+/// found in another order, are printed starting from another item and with
+/// other positions, and node and update counts are not comparable across
+/// engines.
+pub struct DancingCells {
+    item_names: Vec<String>,
+    item_index: HashMap<String, usize, FnvBuildHasher>,
+    node_item: Vec<u32>,
+    node_option: Vec<u32>,
+    option_start: Vec<u32>,
+    option_data: Vec<OptionData>,
+    option_marks: Vec<u64>,
+    option_active: Vec<bool>,
+    item_active: Vec<bool>,
+    primary: usize,
+    secondary: usize,
+    config: Config,
+}
+
+// Search state built from the options when `dance` starts.
+struct Cells {
+    set: Vec<u32>,
+    start: Vec<u32>,
+    size: Vec<u32>,
+    node_loc: Vec<u32>,
+    active: [Vec<u32>; 2],
+    active_len: [usize; 2],
+    item_pos: Vec<u32>,
+}
+
+impl DancingCells {
+    pub fn new(config: &Config, primary_items: &[&str], secondary_items: &[&str]) -> Self {
+        let n1 = primary_items.len();
+        let n2 = secondary_items.len();
+        let n = n1 + n2;
+
+        let mut dc = DancingCells {
+            item_names: Vec::with_capacity(n + 1),
+            item_index: HashMap::with_capacity_and_hasher(n, FnvBuildHasher::default()),
+            node_item: Vec::new(),
+            node_option: Vec::new(),
+            option_start: vec![0],
+            option_data: Vec::new(),
+            option_marks: vec![0; (n + 1).div_ceil(64)],
+            option_active: Vec::new(),
+            item_active: vec![true; n + 1],
+            primary: n1,
+            secondary: n2,
+            config: config.clone(),
+        };
+
+        dc.item_names.push(String::new());
+
+        for item in primary_items.iter().chain(secondary_items) {
+            if dc.item_index.contains_key(*item) {
+                panic!("Item names must be unique.");
+            }
+
+            dc.item_index
+                .insert(String::from(*item), dc.item_names.len());
+            dc.item_names.push(String::from(*item));
+        }
+
+        dc
+    }
+
+    /// Copies the items and options of an instance built with `DancingLinks`.
+    pub fn from_links(dlx: &DancingLinks) -> Self {
+        let names: Vec<&str> = (1..=dlx.get_item_count())
+            .map(|i| dlx.get_item_name(i))
+            .collect();
+
+        let (primary_items, secondary_items) = names.split_at(dlx.get_primary());
+
+        let mut dc = DancingCells::new(&dlx.config, primary_items, secondary_items);
+
+        for (k, items) in dlx.get_option_items().iter().enumerate() {
+            dc.add_option_items(items, dlx.get_option_data(k + 1).clone());
+            dc.option_active[k] = dlx.is_option_active(k + 1);
+        }
+
+        for i in 1..=dlx.get_item_count() {
+            dc.item_active[i] = dlx.is_item_active(i);
+        }
+
+        dc
+    }
+
+    /// Appends an item after the primary or the secondary ones and returns its
+    /// index. Adding a primary item shifts the indices of the secondary ones,
+    /// while options keep their numbers.
+    pub fn append_item(&mut self, name: &str, primary: bool) -> usize {
+        if self.item_index.contains_key(name) {
+            panic!("Item names must be unique.");
+        }
+
+        let n1 = self.primary;
+        let shift = |i: usize| if primary && i > n1 { i + 1 } else { i };
+
+        let mut primary_items: Vec<&str> = (1..=n1).map(|i| self.get_item_name(i)).collect();
+        let mut secondary_items: Vec<&str> = (n1 + 1..=self.get_item_count())
+            .map(|i| self.get_item_name(i))
+            .collect();
+
+        if primary {
+            primary_items.push(name);
+        } else {
+            secondary_items.push(name);
+        }
+
+        let mut dc = DancingCells::new(&self.config, &primary_items, &secondary_items);
+
+        for (k, items) in self.get_option_items().iter().enumerate() {
+            let items: Vec<usize> = items.iter().map(|&i| shift(i)).collect();
+
+            dc.add_option_items(&items, self.option_data[k].clone());
+        }
+
+        dc.option_active.clone_from(&self.option_active);
+
+        for (i, &active) in self.item_active.iter().enumerate() {
+            dc.item_active[shift(i)] = active;
+        }
+
+        *self = dc;
+
+        self.find_item(name).unwrap()
+    }
+
+    /// Adds an option and returns its number, which never changes.
+    pub fn add_option(&mut self, option_str: &str) -> usize {
+        self.add_option_with_data(option_str, OptionData::default())
+    }
+
+    pub fn add_option_with_data(&mut self, option_str: &str, data: OptionData) -> usize {
+        let items: Vec<usize> = option_str
+            .split_whitespace()
+            .map(|item_name| match self.find_item(item_name) {
+                Some(i) => i,
+                None => panic!("Options must contain known items."),
+            })
+            .collect();
+
+        self.add_option_items(&items, data)
+    }
+
+    /// Adds an option given by item indices, as returned by `find_item`.
+    pub fn add_option_items(&mut self, items: &[usize], data: OptionData) -> usize {
+        let option = self.option_data.len() as u32;
+
+        for &i in items {
+            if i == 0 || i > self.get_item_count() {
+                panic!("Options must contain known items.");
+            }
+
+            let mark = 1 << (i % 64);
+
+            if self.option_marks[i / 64] & mark != 0 {
+                panic!("Options must contain unique items.");
+            }
+
+            self.option_marks[i / 64] |= mark;
+
+            self.node_item.push(i as u32);
+            self.node_option.push(option);
+        }
+
+        for &i in items {
+            self.option_marks[i / 64] = 0;
+        }
+
+        self.option_start.push(
+            self.node_item
+                .len()
+                .try_into()
+                .expect("Instances are limited to 2^32 nodes."),
+        );
+        self.option_data.push(data);
+        self.option_active.push(true);
+
+        self.option_data.len()
+    }
+
+    /// Leaves option `option` out of the instance until it is reactivated.
+    pub fn deactivate_option(&mut self, option: usize) {
+        self.option_active[option - 1] = false;
+    }
+
+    pub fn reactivate_option(&mut self, option: usize) {
+        self.option_active[option - 1] = true;
+    }
+
+    /// Leaves item `i` out of the instance, along with every option that
+    /// contains it, until it is reactivated.
+    pub fn deactivate_item(&mut self, i: usize) {
+        if i == 0 || i > self.get_item_count() {
+            panic!("Item {i} does not belong to the instance.");
+        }
+
+        self.item_active[i] = false;
+    }
+
+    pub fn reactivate_item(&mut self, i: usize) {
+        if i == 0 || i > self.get_item_count() {
+            panic!("Item {i} does not belong to the instance.");
+        }
+
+        self.item_active[i] = true;
+    }
+
+    pub fn is_option_active(&self, option: usize) -> bool {
+        self.option_active[option - 1]
+    }
+
+    pub fn is_item_active(&self, i: usize) -> bool {
+        self.item_active[i]
+    }
+
+    pub fn dance(&mut self) -> Statistics {
+        self.dance_with(&[])
+    }
+
+    /// Solves the instance with the given options, numbered from 1 in input
+    /// order, forced into every solution, as `DancingLinks::dance_with` does.
+    /// Clashing assumptions give no solutions.
+    pub fn dance_with(&mut self, assumptions: &[usize]) -> Statistics {
+        self.dance_recording(assumptions, None)
+    }
+
+    /// Solves the instance as `dance_with` does, recording the search tree
+    /// into `tree` as it goes.
+    pub fn dance_with_tree(&mut self, assumptions: &[usize], tree: &mut SearchTree) -> Statistics {
+        self.dance_recording(assumptions, Some(tree))
+    }
+
+    fn dance_recording(
+        &mut self,
+        assumptions: &[usize],
+        tree: Option<&mut SearchTree>,
+    ) -> Statistics {
+        let mut cells = self.build_cells();
+        let mut forced = Vec::with_capacity(assumptions.len());
+
+        for &k in assumptions {
+            if k == 0 || k > self.get_option_count() {
+                panic!("Assumptions must be options of the instance.");
+            }
+
+            let nodes = self.option_start[k - 1] as usize..self.option_start[k] as usize;
+
+            // Inactive items and the items of earlier assumptions are not in
+            // the active sets.
+            if !self.option_active[k - 1]
+                || nodes
+                    .clone()
+                    .any(|y| !cells.is_active(self.node_item[y] as usize, self.primary))
+            {
+                return Statistics::finished(Counts::default(), Duration::ZERO);
+            }
+
+            let (position, length) = self.get_option_position(&cells, k - 1);
+
+            for y in nodes.clone() {
+                self.cover(&mut cells, self.node_item[y] as usize);
+            }
+
+            forced.push((nodes.start, position, length));
+        }
+
+        self.search(&mut cells, &forced, tree)
+    }
+
+    // Runs the search on the active items of `cells`. The options in
+    // `forced`, given by their first node with their positions, are already
+    // covered and are printed with every solution.
+    fn search(
+        &self,
+        cells: &mut Cells,
+        forced: &[(usize, Option<usize>, usize)],
+        mut tree: Option<&mut SearchTree>,
+    ) -> Statistics {
+        let mut monitor = Monitor::new(&self.config);
+
+        let depth = self.primary + 1;
+
+        let mut item = vec![0; depth];
+        let mut choice = vec![0; depth];
+        let mut position = vec![0; depth];
+        let mut length = vec![0; depth];
+
+        let mut level = 0;
+        let mut enter = true;
+
+        let mut rng = match self.config.get_randomization_seed() {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_os_rng(),
+        };
+
+        let mut counts = Counts::default();
+
+        let mut profile = self
+            .config
+            .is_profiling()
+            .then(|| Profile::new(&self.item_names));
+
+        let mut branch_table = self.config.get_branch_depth().map(BranchTable::new);
+
+        let show_first = self.config.show_first();
+        let solution_interval = self.config.get_solution_interval();
+        let solution_limit = self.config.get_solution_limit();
+        let format = self.config.get_format();
+        let mut printed_solutions = 0;
+
+        let randomize = self.config.get_randomization_seed().is_some();
+        let check_links = self.config.check_links();
+
+        // The deepest partial solution so far that is not a solution, shown if the
+        // search is stopped.
+        let mut deepest = forced.to_vec();
+        let mut deepest_uncovered = get_uncovered_items(cells);
+
+        loop {
+            if check_links {
+                let mut covered: Vec<bool> = self.item_active.iter().map(|&a| !a).collect();
+
+                for x in forced
+                    .iter()
+                    .map(|&(x, _, _)| x)
+                    .chain(choice[..level].iter().copied())
+                {
+                    let o = self.node_option[x] as usize;
+
+                    for y in self.option_start[o]..self.option_start[o + 1] {
+                        covered[self.node_item[y as usize] as usize] = true;
+                    }
+                }
+
+                if !enter {
+                    covered[item[level]] = true;
+                }
+
+                if let Err(err) = self.check_cells(cells, &covered) {
+                    panic!("Link check failed after {} nodes: {}", counts.nodes, err);
+                }
+            }
+
+            let stop = monitor.check(
+                &counts,
+                None,
+                enter,
+                || {
+                    if enter {
+                        cells.active_len[0] > 0
+                    } else {
+                        position[level] < length[level]
+                    }
+                },
+                || (0..level).map(|l| (Some(position[l]), length[l])).collect(),
+            );
+
+            if let Some(stop) = stop {
+                let solution = get_solution(forced, &choice[..level], &position, &length);
+
+                let partial = output::Partial {
+                    options: self.get_chosen_options(&solution),
+                    explored: stop.explored,
+                    deepest: self.get_chosen_options(&deepest),
+                    uncovered: deepest_uncovered
+                        .iter()
+                        .map(|&i| self.item_names[i].as_str())
+                        .collect(),
+                };
+
+                output::print_partial(format, &partial);
+
+                if let Some(table) = &mut branch_table {
+                    table.finish(0, counts.solutions, counts.nodes, monitor.elapsed());
+                }
+
+                return Statistics {
+                    profile,
+                    branches: branch_table,
+                    ..Statistics::stopped(counts, monitor.elapsed(), stop.reason)
+                };
+            }
+
+            if enter {
+                enter = false;
+                counts.nodes += 1;
+
+                if cells.active_len[0] == 0 {
+                    if counts.max_level < level + 1 {
+                        counts.max_level = level + 1;
+                    }
+
+                    counts.solutions += 1;
+
+                    if let Some(profile) = &mut profile {
+                        profile.add_solution(level);
+                    }
+
+                    if let Some(tree) = tree.as_deref_mut() {
+                        tree.add_node(
+                            level,
+                            || NodeKind::Solution,
+                            || {
+                                self.get_tree_option(
+                                    choice[level - 1],
+                                    position[level - 1],
+                                    length[level - 1],
+                                )
+                            },
+                        );
+                    }
+
+                    if (show_first && counts.solutions == 1)
+                        || (solution_interval > 0 && counts.solutions % solution_interval == 0)
+                    {
+                        let solution = get_solution(forced, &choice[..level], &position, &length);
+
+                        output::print_solution(
+                            format,
+                            counts.solutions,
+                            &self.get_chosen_options(&solution),
+                            printed_solutions == 0,
+                        );
+
+                        printed_solutions += 1;
+                    }
+
+                    if solution_limit == Some(counts.solutions) {
+                        if let Some(table) = &mut branch_table {
+                            table.finish(0, counts.solutions, counts.nodes, monitor.elapsed());
+                        }
+
+                        return Statistics {
+                            profile,
+                            branches: branch_table,
+                            ..Statistics::stopped(
+                                counts,
+                                monitor.elapsed(),
+                                StopReason::LimitReached,
+                            )
+                        };
+                    }
+
+                    if level == 0 {
+                        break;
+                    }
+
+                    level -= 1;
+
+                    self.uncover_option(cells, choice[level]);
+
+                    continue;
+                }
+
+                let mut min_length = usize::MAX;
+                let mut i = 0;
+
+                for &j in &cells.active[0][..cells.active_len[0]] {
+                    let length = cells.size[j as usize] as usize;
+
+                    if length < min_length {
+                        min_length = length;
+                        i = j as usize;
+
+                        if min_length == 0 {
+                            break;
+                        }
+                    }
+                }
+
+                if randomize {
+                    let mut candidates = 0;
+
+                    for &j in &cells.active[0][..cells.active_len[0]] {
+                        if cells.size[j as usize] as usize == min_length {
+                            candidates += 1;
+
+                            if rng.random_range(0..candidates) == 0 {
+                                i = j as usize;
+                            }
+                        }
+                    }
+                }
+
+                if counts.max_degree < min_length {
+                    counts.max_degree = min_length;
+                }
+
+                if let Some(profile) = &mut profile {
+                    profile.add_branch(level, i, min_length);
+                }
+
+                if let Some(tree) = tree.as_deref_mut() {
+                    tree.add_node(
+                        level,
+                        || NodeKind::Branch {
+                            item: self.item_names[i].clone(),
+                            degree: min_length,
+                        },
+                        || {
+                            self.get_tree_option(
+                                choice[level - 1],
+                                position[level - 1],
+                                length[level - 1],
+                            )
+                        },
+                    );
+                }
+
+                counts.updates += self.cover(cells, i);
+
+                item[level] = i;
+                position[level] = 0;
+                length[level] = min_length;
+            }
+
+            let i = item[level];
+
+            if position[level] == length[level] {
+                self.uncover(cells, i);
+
+                if let Some(table) = &mut branch_table {
+                    table.finish(level, counts.solutions, counts.nodes, monitor.elapsed());
+                }
+
+                if level == 0 {
+                    break;
+                }
+
+                level -= 1;
+
+                self.uncover_option(cells, choice[level]);
+
+                continue;
+            }
+
+            let x = cells.set[cells.start[i] as usize + position[level]] as usize;
+
+            choice[level] = x;
+            position[level] += 1;
+
+            if let Some(table) = &mut branch_table
+                && level < table.get_depth()
+            {
+                table.start(
+                    level,
+                    self.get_tree_option(x, position[level], length[level]),
+                    counts.solutions,
+                    counts.nodes,
+                    monitor.elapsed(),
+                );
+            }
+
+            let o = self.node_option[x] as usize;
+
+            for y in self.option_start[o] as usize..self.option_start[o + 1] as usize {
+                if y != x {
+                    counts.updates += self.cover(cells, self.node_item[y] as usize);
+                }
+            }
+
+            level += 1;
+            enter = true;
+
+            if counts.max_level < level {
+                counts.max_level = level;
+            }
+
+            if cells.active_len[0] > 0 && deepest.len() < forced.len() + level {
+                deepest = get_solution(forced, &choice[..level], &position, &length);
+                deepest_uncovered = get_uncovered_items(cells);
+            }
+        }
+
+        if let Some(table) = &mut branch_table {
+            table.finish(0, counts.solutions, counts.nodes, monitor.elapsed());
+        }
+
+        Statistics {
+            profile,
+            branches: branch_table,
+            ..Statistics::finished(counts, monitor.elapsed())
+        }
+    }
+
+    fn build_cells(&self) -> Cells {
+        let n = self.get_item_count();
+
+        // Options left out of the instance are not put in any set.
+        let usable: Vec<bool> = (0..self.get_option_count())
+            .map(|o| {
+                self.option_active[o]
+                    && (self.option_start[o]..self.option_start[o + 1])
+                        .all(|y| self.item_active[self.node_item[y as usize] as usize])
+            })
+            .collect();
+
+        let mut start = vec![0; n + 2];
+
+        for (x, &i) in self.node_item.iter().enumerate() {
+            if usable[self.node_option[x] as usize] {
+                start[i as usize + 1] += 1;
+            }
+        }
+
+        for i in 1..=n + 1 {
+            start[i] += start[i - 1];
+        }
+
+        let mut size = vec![0; n + 1];
+        let mut set = vec![0; start[n + 1] as usize];
+        let mut node_loc = vec![0; self.node_item.len()];
+
+        for (x, &i) in self.node_item.iter().enumerate() {
+            if !usable[self.node_option[x] as usize] {
+                continue;
+            }
+
+            let i = i as usize;
+            let loc = start[i] + size[i];
+
+            set[loc as usize] = x as u32;
+            node_loc[x] = loc;
+            size[i] += 1;
+        }
+
+        // Primary and secondary items start in their own sets in input order,
+        // followed by the inactive ones.
+        let mut active = [
+            Vec::with_capacity(self.primary),
+            Vec::with_capacity(self.secondary),
+        ];
+        let mut active_len = [0; 2];
+
+        for (kind, items) in [(0, 1..=self.primary), (1, self.primary + 1..=n)] {
+            active[kind].extend(
+                items
+                    .clone()
+                    .filter(|&i| self.item_active[i])
+                    .map(|i| i as u32),
+            );
+            active_len[kind] = active[kind].len();
+            active[kind].extend(items.filter(|&i| !self.item_active[i]).map(|i| i as u32));
+        }
+
+        let mut item_pos = vec![0; n + 1];
+
+        for items in &active {
+            for (p, &i) in items.iter().enumerate() {
+                item_pos[i as usize] = p as u32;
+            }
+        }
+
+        Cells {
+            set,
+            start,
+            size,
+            node_loc,
+            active,
+            active_len,
+            item_pos,
+        }
+    }
+
+    fn cover(&self, cells: &mut Cells, i: usize) -> usize {
+        let mut updates = 1;
+
+        cells.deactivate(i, self.primary);
+
+        let start = cells.start[i] as usize;
+
+        for k in start..start + cells.size[i] as usize {
+            let x = cells.set[k] as usize;
+            let o = self.node_option[x] as usize;
+
+            for y in self.option_start[o] as usize..self.option_start[o + 1] as usize {
+                let j = self.node_item[y] as usize;
+
+                if y != x && cells.is_active(j, self.primary) {
+                    cells.remove(y, j);
+                    updates += 1;
+                }
+            }
+        }
+
+        updates
+    }
+
+    fn uncover(&self, cells: &mut Cells, i: usize) {
+        let start = cells.start[i] as usize;
+
+        for k in (start..start + cells.size[i] as usize).rev() {
+            let x = cells.set[k] as usize;
+            let o = self.node_option[x] as usize;
+
+            for y in (self.option_start[o] as usize..self.option_start[o + 1] as usize).rev() {
+                let j = self.node_item[y] as usize;
+
+                if y != x && cells.is_active(j, self.primary) {
+                    cells.size[j] += 1;
+                }
+            }
+        }
+
+        cells.reactivate(i, self.primary);
+    }
+
+    // Undoes the covering of the items of the option of `x` other than the
+    // one it was chosen for.
+    fn uncover_option(&self, cells: &mut Cells, x: usize) {
+        let o = self.node_option[x] as usize;
+
+        for y in (self.option_start[o] as usize..self.option_start[o + 1] as usize).rev() {
+            if y != x {
+                self.uncover(cells, self.node_item[y] as usize);
+            }
+        }
+    }
+
+    fn get_chosen_options(
+        &self,
+        solution: &[(usize, Option<usize>, usize)],
+    ) -> Vec<ChosenOption<'_>> {
+        solution
+            .iter()
+            .map(|&(x, position, length)| self.get_chosen_option(x, position, length))
+            .collect()
+    }
+
+    // Gives the number and text of the option of node `x`, as an edge of the
+    // search tree or a branch counted by `--branch-counts`.
+    fn get_tree_option(&self, x: usize, position: usize, length: usize) -> (usize, String) {
+        let option = self.get_chosen_option(x, Some(position), length);
+
+        (option.option, option.to_text())
+    }
+
+    // Gives the position of option `o` among the options of its first item
+    // and how many they are, as `DancingLinks` lists them between searches.
+    // Gives the position of option `o` among the active options of its first
+    // item in `cells`, if it is one of them, and their number.
+    fn get_option_position(&self, cells: &Cells, o: usize) -> (Option<usize>, usize) {
+        let x = self.option_start[o];
+        let i = self.node_item[x as usize] as usize;
+
+        let start = cells.start[i] as usize;
+        let active = &cells.set[start..start + cells.size[i] as usize];
+
+        let position = active.iter().position(|&y| y == x).map(|k| k + 1);
+
+        (position, active.len())
+    }
+
+    fn get_chosen_option(
+        &self,
+        x: usize,
+        position: Option<usize>,
+        length: usize,
+    ) -> ChosenOption<'_> {
+        let o = self.node_option[x] as usize;
+        let start = self.option_start[o] as usize;
+
+        ChosenOption {
+            option: o + 1,
+            items: (start..self.option_start[o + 1] as usize)
+                .map(|y| self.item_names[self.node_item[y] as usize].as_str())
+                .collect(),
+            first: x - start,
+            position,
+            length,
+            data: &self.option_data[o],
+        }
+    }
+
+    pub fn find_item(&self, name: &str) -> Option<usize> {
+        self.item_index.get(name).copied()
+    }
+
+    pub fn get_item_name(&self, i: usize) -> &str {
+        &self.item_names[i]
+    }
+
+    /// Returns the option made of exactly `items`, in any order.
+    pub fn find_option(&self, items: &[usize]) -> Option<usize> {
+        let mut key = items.to_vec();
+        key.sort_unstable();
+
+        key.first()?;
+
+        (0..self.get_option_count())
+            .find(|&o| {
+                let nodes = self.option_start[o] as usize..self.option_start[o + 1] as usize;
+
+                if nodes.len() != key.len() {
+                    return false;
+                }
+
+                let mut option: Vec<usize> = nodes.map(|y| self.node_item[y] as usize).collect();
+                option.sort_unstable();
+
+                option == key
+            })
+            .map(|o| o + 1)
+    }
+
+    /// Checks that the sparse sets a search starts from are consistent, as
+    /// `DancingLinks::check_links` does for its links. Returns the first
+    /// violation found.
+    pub fn check_links(&self) -> Result<(), String> {
+        let covered: Vec<bool> = self.item_active.iter().map(|&a| !a).collect();
+
+        self.check_cells(&self.build_cells(), &covered)
+    }
+
+    // Checks that every set holds the nodes of its item at the locations
+    // recorded for them, that the active options of every uncovered item are
+    // exactly the ones with no covered item, and that the active items are
+    // exactly the uncovered ones.
+    fn check_cells(&self, cells: &Cells, covered: &[bool]) -> Result<(), String> {
+        let n1 = self.primary;
+        let n = self.get_item_count();
+
+        let name = |i: usize| format!("{} ({})", self.item_names[i], i);
+
+        for (kind, items, kind_name) in [(0, 1..=n1, "primary"), (1, n1 + 1..=n, "secondary")] {
+            if cells.active[kind].len() != items.clone().count() {
+                return Err(format!(
+                    "The set of {} items has {} elements instead of {}.",
+                    kind_name,
+                    cells.active[kind].len(),
+                    items.count(),
+                ));
+            }
+
+            for (p, &i) in cells.active[kind].iter().enumerate() {
+                let i = i as usize;
+
+                if !items.contains(&i) {
+                    return Err(format!("Item {} is in the set of {} items.", i, kind_name));
+                }
+
+                if cells.item_pos[i] as usize != p {
+                    return Err(format!(
+                        "Item {} is at {} in its set but recorded at {}.",
+                        name(i),
+                        p,
+                        cells.item_pos[i],
+                    ));
+                }
+
+                if (p < cells.active_len[kind]) == covered[i] {
+                    return Err(match covered[i] {
+                        true => format!("Item {} is covered but active.", name(i)),
+                        false => format!("Item {} is uncovered but inactive.", name(i)),
+                    });
+                }
+            }
+        }
+
+        for i in 1..=n {
+            let start = cells.start[i] as usize;
+            let end = cells.start[i + 1] as usize;
+
+            if (cells.size[i] as usize) > end - start {
+                return Err(format!(
+                    "Item {} has {} active options out of {}.",
+                    name(i),
+                    cells.size[i],
+                    end - start,
+                ));
+            }
+
+            for k in start..end {
+                let x = cells.set[k] as usize;
+
+                if self.node_item[x] as usize != i {
+                    return Err(format!("Node {} is in the set of item {}.", x, name(i)));
+                }
+
+                if cells.node_loc[x] as usize != k {
+                    return Err(format!(
+                        "Node {} is at {} in the set of item {} but recorded at {}.",
+                        x,
+                        k,
+                        name(i),
+                        cells.node_loc[x],
+                    ));
+                }
+
+                let o = self.node_option[x] as usize;
+                let live = (self.option_start[o]..self.option_start[o + 1])
+                    .all(|y| !covered[self.node_item[y as usize] as usize]);
+
+                if !covered[i] && (k < start + cells.size[i] as usize) != live {
+                    return Err(match live {
+                        true => format!("Option {} is missing from item {}.", o + 1, name(i)),
+                        false => format!("Option {} is still active in item {}.", o + 1, name(i)),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Looks for input that is valid but likely a mistake, as
+    /// `DancingLinks::get_warnings` does.
+    pub fn get_warnings(&self) -> Vec<String> {
+        utils::get_warnings(
+            self.primary,
+            &self.item_names,
+            &self.get_option_items(),
+            &self.option_data,
+        )
+    }
+
+    /// Prints the given options, numbered from 1, as a solution.
+    pub fn print_options(&self, index: usize, options: &[usize], first: bool) {
+        let cells = self.build_cells();
+
+        let solution: Vec<(usize, Option<usize>, usize)> = options
+            .iter()
+            .map(|&k| {
+                let (position, length) = self.get_option_position(&cells, k - 1);
+
+                (self.option_start[k - 1] as usize, position, length)
+            })
+            .collect();
+
+        output::print_solution(
+            self.config.get_format(),
+            index,
+            &self.get_chosen_options(&solution),
+            first,
+        );
+    }
+
+    pub fn get_option_data(&self, option: usize) -> &OptionData {
+        &self.option_data[option - 1]
+    }
+
+    pub fn get_option_items(&self) -> Vec<Vec<usize>> {
+        (0..self.get_option_count())
+            .map(|o| {
+                (self.option_start[o]..self.option_start[o + 1])
+                    .map(|y| self.node_item[y as usize] as usize)
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn get_primary(&self) -> usize {
+        self.primary
+    }
+
+    pub fn get_secondary(&self) -> usize {
+        self.secondary
+    }
+
+    pub fn get_item_count(&self) -> usize {
+        self.primary + self.secondary
+    }
+
+    pub fn get_option_count(&self) -> usize {
+        self.option_data.len()
+    }
+}
+
+impl Cells {
+    fn is_active(&self, i: usize, primary: usize) -> bool {
+        let kind = (i > primary) as usize;
+
+        (self.item_pos[i] as usize) < self.active_len[kind]
+    }
+
+    fn deactivate(&mut self, i: usize, primary: usize) {
+        let kind = (i > primary) as usize;
+
+        let p = self.item_pos[i] as usize;
+        let last = self.active_len[kind] - 1;
+        let j = self.active[kind][last] as usize;
+
+        self.active[kind].swap(p, last);
+        self.item_pos[j] = p as u32;
+        self.item_pos[i] = last as u32;
+        self.active_len[kind] = last;
+    }
+
+    // Items are reactivated in the reverse order they were deactivated, so
+    // `i` is right after the active ones.
+    fn reactivate(&mut self, i: usize, primary: usize) {
+        let kind = (i > primary) as usize;
+
+        self.active_len[kind] += 1;
+    }
+
+    // Removes node `y` from the active options of item `i`.
+    fn remove(&mut self, y: usize, i: usize) {
+        let p = self.node_loc[y] as usize;
+        let last = (self.start[i] + self.size[i] - 1) as usize;
+        let z = self.set[last] as usize;
+
+        self.set.swap(p, last);
+        self.node_loc[z] = p as u32;
+        self.node_loc[y] = last as u32;
+        self.size[i] -= 1;
+    }
+}
+
+// Lists the primary items that are not covered yet, in input order.
+fn get_uncovered_items(cells: &Cells) -> Vec<usize> {
+    let mut items: Vec<usize> = cells.active[0][..cells.active_len[0]]
+        .iter()
+        .map(|&i| i as usize)
+        .collect();
+
+    items.sort_unstable();
+
+    items
+}
+
+// Pairs the nodes chosen at each level with their positions in the sets of
+// the items the search branched on, after the forced options.
+fn get_solution(
+    forced: &[(usize, Option<usize>, usize)],
+    choice: &[usize],
+    position: &[usize],
+    length: &[usize],
+) -> Vec<(usize, Option<usize>, usize)> {
+    let chosen = (0..choice.len()).map(|l| (choice[l], Some(position[l]), length[l]));
+
+    forced.iter().copied().chain(chosen).collect()
+}
@@ -1,3 +1,45 @@
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Ndjson,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    Text,
+    Json,
+    Matrix,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Dimacs,
+    Opb,
+    Lp,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TreeFormat {
+    Dot,
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CnfEncoding {
+    Pairwise,
+    Sequential,
+    Commander,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    Auto,
+    Dlx,
+    Bitset,
+    Cells,
+}
+
 #[derive(Clone)]
 pub struct Config {
     show_first: bool,
@@ -7,7 +49,32 @@ pub struct Config {
     report_delta: u64,
     randomization_seed: Option<u64>,
     timeout: Option<u64>,
-    verbose: bool,
+    verbosity: usize,
+    choice_depth: usize,
+    format: OutputFormat,
+    input_format: InputFormat,
+    export_format: Option<ExportFormat>,
+    cnf_encoding: CnfEncoding,
+    model_file: Option<String>,
+    verify_file: Option<String>,
+    save_file: Option<String>,
+    load_file: Option<String>,
+    assume_file: Option<String>,
+    batch_delimiter: Option<String>,
+    jobs: usize,
+    unique: bool,
+    max_solutions: Option<usize>,
+    max_nodes: Option<usize>,
+    max_updates: Option<usize>,
+    report_nodes: Option<usize>,
+    tree_file: Option<String>,
+    tree_format: TreeFormat,
+    tree_depth: Option<usize>,
+    tree_nodes: usize,
+    profile: bool,
+    check_links: bool,
+    branch_depth: Option<usize>,
+    engine: Engine,
 }
 
 impl Config {
@@ -20,7 +87,32 @@ impl Config {
             report_delta: 5,
             randomization_seed: None,
             timeout: None,
-            verbose: false,
+            verbosity: 0,
+            choice_depth: 10,
+            format: OutputFormat::Text,
+            input_format: InputFormat::Text,
+            export_format: None,
+            cnf_encoding: CnfEncoding::Sequential,
+            model_file: None,
+            verify_file: None,
+            save_file: None,
+            load_file: None,
+            assume_file: None,
+            batch_delimiter: None,
+            jobs: 1,
+            unique: false,
+            max_solutions: None,
+            max_nodes: None,
+            max_updates: None,
+            report_nodes: None,
+            tree_file: None,
+            tree_format: TreeFormat::Dot,
+            tree_depth: None,
+            tree_nodes: 10000,
+            profile: false,
+            check_links: false,
+            branch_depth: None,
+            engine: Engine::Auto,
         };
 
         let mut args = args.iter();
@@ -30,6 +122,9 @@ impl Config {
 
             match arg {
                 "--show-first" | "-f" => config.show_first = true,
+                "--unique" | "-u" => config.unique = true,
+                "--profile" | "-P" => config.profile = true,
+                "--check-links" | "-k" => config.check_links = true,
                 "--help" | "-h" => {
                     config.help = true;
 
@@ -95,11 +190,223 @@ impl Config {
                         return Err(t_err_str);
                     }
                 }
-                "--verbose" | "-v" => config.verbose = true,
+                "--verbose" | "-v" => config.verbosity += 1,
+                "--verbosity" | "-V" => {
+                    let v_err_str = "--verbosity (-V) requires an integer argument";
+
+                    match args.next().map(|n| n.parse()) {
+                        Some(Ok(v)) => config.verbosity = v,
+                        _ => return Err(v_err_str),
+                    }
+                }
+                "--choice-depth" | "-c" => {
+                    let c_err_str = "--choice-depth (-c) requires an integer argument";
+
+                    match args.next().map(|n| n.parse()) {
+                        Some(Ok(c)) => config.choice_depth = c,
+                        _ => return Err(c_err_str),
+                    }
+                }
+                "--format" | "-F" => {
+                    let f_err_str = "--format (-F) requires one of text, json or ndjson";
+
+                    config.format = match args.next().map(|f| f.as_str()) {
+                        Some("text") => OutputFormat::Text,
+                        Some("json") => OutputFormat::Json,
+                        Some("ndjson") => OutputFormat::Ndjson,
+                        _ => return Err(f_err_str),
+                    };
+                }
+                "--input" | "-I" => {
+                    let in_err_str = "--input (-I) requires one of text, json or matrix";
+
+                    config.input_format = match args.next().map(|f| f.as_str()) {
+                        Some("text") => InputFormat::Text,
+                        Some("json") => InputFormat::Json,
+                        Some("matrix") => InputFormat::Matrix,
+                        _ => return Err(in_err_str),
+                    };
+                }
+                "--engine" | "-E" => {
+                    let engine_err_str = "--engine (-E) requires one of auto, dlx, bitset or cells";
+
+                    config.engine = match args.next().map(|e| e.as_str()) {
+                        Some("auto") => Engine::Auto,
+                        Some("dlx") => Engine::Dlx,
+                        Some("bitset") => Engine::Bitset,
+                        Some("cells") => Engine::Cells,
+                        _ => return Err(engine_err_str),
+                    };
+                }
+                "--export" | "-x" => {
+                    let x_err_str = "--export (-x) requires one of dimacs, opb or lp";
+
+                    config.export_format = match args.next().map(|f| f.as_str()) {
+                        Some("dimacs") => Some(ExportFormat::Dimacs),
+                        Some("opb") => Some(ExportFormat::Opb),
+                        Some("lp") => Some(ExportFormat::Lp),
+                        _ => return Err(x_err_str),
+                    };
+                }
+                "--cnf-encoding" | "-e" => {
+                    let e_err_str =
+                        "--cnf-encoding (-e) requires one of pairwise, sequential or commander";
+
+                    config.cnf_encoding = match args.next().map(|e| e.as_str()) {
+                        Some("pairwise") => CnfEncoding::Pairwise,
+                        Some("sequential") => CnfEncoding::Sequential,
+                        Some("commander") => CnfEncoding::Commander,
+                        _ => return Err(e_err_str),
+                    };
+                }
+                "--decode" | "-d" => {
+                    let d_err_str = "--decode (-d) requires a file argument";
+
+                    match args.next() {
+                        Some(f) => config.model_file = Some(f.clone()),
+                        None => return Err(d_err_str),
+                    }
+                }
+                "--verify" | "-C" => {
+                    let verify_err_str = "--verify (-C) requires a file argument";
+
+                    match args.next() {
+                        Some(f) => config.verify_file = Some(f.clone()),
+                        None => return Err(verify_err_str),
+                    }
+                }
+                "--save" | "-S" => {
+                    let save_err_str = "--save (-S) requires a file argument";
+
+                    match args.next() {
+                        Some(f) => config.save_file = Some(f.clone()),
+                        None => return Err(save_err_str),
+                    }
+                }
+                "--load" | "-L" => {
+                    let load_err_str = "--load (-L) requires a file argument";
+
+                    match args.next() {
+                        Some(f) => config.load_file = Some(f.clone()),
+                        None => return Err(load_err_str),
+                    }
+                }
+                "--batch" | "-b" => {
+                    let b_err_str = "--batch (-b) requires a delimiter argument";
+
+                    match args.next() {
+                        Some(d) if !d.trim().is_empty() => {
+                            config.batch_delimiter = Some(String::from(d.trim()))
+                        }
+                        _ => return Err(b_err_str),
+                    }
+                }
+                "--max-solutions" | "-M" => {
+                    let m_err_str = "--max-solutions (-M) requires a positive integer argument";
+
+                    match args.next().map(|n| n.parse()) {
+                        Some(Ok(m)) if m > 0 => config.max_solutions = Some(m),
+                        _ => return Err(m_err_str),
+                    }
+                }
+                "--max-nodes" | "-N" => {
+                    let n_err_str = "--max-nodes (-N) requires an integer argument";
+
+                    match args.next().map(|n| n.parse()) {
+                        Some(Ok(n)) => config.max_nodes = Some(n),
+                        _ => return Err(n_err_str),
+                    }
+                }
+                "--max-updates" | "-U" => {
+                    let u_err_str = "--max-updates (-U) requires an integer argument";
+
+                    match args.next().map(|n| n.parse()) {
+                        Some(Ok(u)) => config.max_updates = Some(u),
+                        _ => return Err(u_err_str),
+                    }
+                }
+                "--report-nodes" | "-R" => {
+                    let rn_err_str = "--report-nodes (-R) requires a positive integer argument";
+
+                    match args.next().map(|n| n.parse()) {
+                        Some(Ok(n)) if n > 0 => config.report_nodes = Some(n),
+                        _ => return Err(rn_err_str),
+                    }
+                }
+                "--jobs" | "-j" => {
+                    let j_err_str = "--jobs (-j) requires a positive integer argument";
+
+                    match args.next().map(|n| n.parse()) {
+                        Some(Ok(j)) if j > 0 => config.jobs = j,
+                        _ => return Err(j_err_str),
+                    }
+                }
+                "--assume" | "-a" => {
+                    let assume_err_str = "--assume (-a) requires a file argument";
+
+                    match args.next() {
+                        Some(f) => config.assume_file = Some(f.clone()),
+                        None => return Err(assume_err_str),
+                    }
+                }
+                "--tree" | "-T" => {
+                    let tree_err_str = "--tree (-T) requires a file argument";
+
+                    match args.next() {
+                        Some(f) => config.tree_file = Some(f.clone()),
+                        None => return Err(tree_err_str),
+                    }
+                }
+                "--tree-format" | "-g" => {
+                    let g_err_str = "--tree-format (-g) requires one of dot or json";
+
+                    config.tree_format = match args.next().map(|g| g.as_str()) {
+                        Some("dot") => TreeFormat::Dot,
+                        Some("json") => TreeFormat::Json,
+                        _ => return Err(g_err_str),
+                    };
+                }
+                "--tree-depth" | "-D" => {
+                    let depth_err_str = "--tree-depth (-D) requires an integer argument";
+
+                    match args.next().map(|n| n.parse()) {
+                        Some(Ok(d)) => config.tree_depth = Some(d),
+                        _ => return Err(depth_err_str),
+                    }
+                }
+                "--branch-counts" | "-B" => {
+                    let b_err_str = "--branch-counts (-B) requires a positive integer argument";
+
+                    match args.next().map(|n| n.parse()) {
+                        Some(Ok(d)) if d > 0 => config.branch_depth = Some(d),
+                        _ => return Err(b_err_str),
+                    }
+                }
+                "--tree-nodes" | "-n" => {
+                    let tn_err_str = "--tree-nodes (-n) requires a positive integer argument";
+
+                    match args.next().map(|n| n.parse()) {
+                        Some(Ok(n)) if n > 0 => config.tree_nodes = n,
+                        _ => return Err(tn_err_str),
+                    }
+                }
+                // Repeated short flags like -vvv.
+                _ if arg.len() > 2
+                    && arg
+                        .strip_prefix('-')
+                        .is_some_and(|v| v.bytes().all(|b| b == b'v')) =>
+                {
+                    config.verbosity += arg.len() - 1
+                }
                 _ => (),
             }
         }
 
+        // The verdict of --unique is shown with its witnesses.
+        if config.unique {
+            config.solution_interval = 1;
+        }
+
         Ok(config)
     }
 
@@ -132,12 +439,129 @@ impl Config {
     }
 
     pub fn is_verbose(&self) -> bool {
-        self.verbose
+        self.verbosity > 0
+    }
+
+    /// Returns how much the search tells about itself on stderr:
+    ///
+    /// - 1: warnings on the input and a longer summary,
+    /// - 2: also every option tried,
+    /// - 3: also dead ends, solutions and the item totals before and after,
+    /// - 4: also the whole state at every node.
+    ///
+    /// Choices and states are shown down to `get_choice_depth`.
+    pub fn get_verbosity(&self) -> usize {
+        self.verbosity
+    }
+
+    pub fn get_choice_depth(&self) -> usize {
+        self.choice_depth
+    }
+
+    pub fn get_format(&self) -> OutputFormat {
+        self.format
+    }
+
+    pub fn get_input_format(&self) -> InputFormat {
+        self.input_format
+    }
+
+    pub fn get_export_format(&self) -> Option<ExportFormat> {
+        self.export_format
+    }
+
+    pub fn get_cnf_encoding(&self) -> CnfEncoding {
+        self.cnf_encoding
+    }
+
+    pub fn get_model_file(&self) -> Option<&str> {
+        self.model_file.as_deref()
+    }
+
+    pub fn get_verify_file(&self) -> Option<&str> {
+        self.verify_file.as_deref()
+    }
+
+    pub fn get_engine(&self) -> Engine {
+        self.engine
+    }
+
+    pub fn get_save_file(&self) -> Option<&str> {
+        self.save_file.as_deref()
+    }
+
+    pub fn get_load_file(&self) -> Option<&str> {
+        self.load_file.as_deref()
+    }
+
+    pub fn get_assume_file(&self) -> Option<&str> {
+        self.assume_file.as_deref()
+    }
+
+    pub fn get_batch_delimiter(&self) -> Option<&str> {
+        self.batch_delimiter.as_deref()
+    }
+
+    pub fn get_jobs(&self) -> usize {
+        self.jobs
+    }
+
+    pub fn is_unique(&self) -> bool {
+        self.unique
+    }
+
+    /// Returns how many solutions to find before stopping, if any.
+    pub fn get_solution_limit(&self) -> Option<usize> {
+        match (self.unique, self.max_solutions) {
+            (true, Some(m)) => Some(m.min(2)),
+            (true, None) => Some(2),
+            (false, m) => m,
+        }
+    }
+
+    pub fn get_max_nodes(&self) -> Option<usize> {
+        self.max_nodes
+    }
+
+    pub fn get_max_updates(&self) -> Option<usize> {
+        self.max_updates
+    }
+
+    pub fn get_report_nodes(&self) -> Option<usize> {
+        self.report_nodes
+    }
+
+    pub fn get_tree_file(&self) -> Option<&str> {
+        self.tree_file.as_deref()
+    }
+
+    pub fn get_tree_format(&self) -> TreeFormat {
+        self.tree_format
+    }
+
+    pub fn get_tree_depth(&self) -> Option<usize> {
+        self.tree_depth
+    }
+
+    pub fn get_tree_nodes(&self) -> usize {
+        self.tree_nodes
+    }
+
+    pub fn get_branch_depth(&self) -> Option<usize> {
+        self.branch_depth
+    }
+
+    pub fn check_links(&self) -> bool {
+        self.check_links
+    }
+
+    pub fn is_profiling(&self) -> bool {
+        self.profile
     }
 
     pub fn show_help(&self) {
         println!(
-"An eXact Cover (XC) solver using Dancing Links (DLX).
+            "An eXact Cover (XC) solver using Dancing Links (DLX).
 
 Usage: ./dlx [OPTIONS]
 
@@ -153,25 +577,121 @@ Then, again via stdin, enter an option, one per line. An option is a set of
 items. These must match the names entered previously and cannot repeat in an
 option. Reading of options ends when reaching end-of-file (EOF).
 
+With -I json the whole instance is instead a JSON object holding the lists
+\"primary\" and \"secondary\" of item names, and the list \"options\". Each
+option is either a list of item names or an object with those names under
+\"items\" and an optional \"label\" and \"payload\", echoed with solutions.
+
+With -I matrix the first line holds the number of columns and, optionally, how
+many of the last ones are secondary. Every other line is a row (an option),
+either as 0/1 entries for all columns or as 1-based column indices. Columns are
+named c1, c2, and so on.
+
+With -b the input holds many instances, each one starting after a line that
+begins with the delimiter, optionally followed by the instance name. They are
+solved in turn, or by several threads with -j, and their results are printed
+in input order tagged with their index and name.
+
+With -a the options listed in a file, one per line by its item names, are
+forced into every solution, as with the givens of a puzzle.
+
+With -T the tree explored by the search is written to a file for Graphviz or as
+JSON: each node is the item branched on, each edge the option tried and each
+leaf a solution or a dead end. Only the first nodes and levels are recorded.
+
+With -C the solutions in a file, as printed by the solver in any format, are
+checked against the instance without searching, reporting every option that is
+not in the instance and every item not covered exactly once.
+
+With -B the solutions, nodes and time under every option tried at the first
+levels of any engine are printed after the search, so that totals can be
+audited or compared between runs.
+
+Each -v makes the output more verbose, as -V does at once: 1 adds warnings on
+the input and a longer summary, 2 prints every option tried, 3 also dead ends,
+solutions and the item totals before and after the search, and 4 the whole
+state at every node. Levels 2 and up trace the dlx engine down to -c levels on
+stderr.
+
+Pressing Ctrl-C stops the search, printing the partial solution and the
+statistics and exiting with code 130, and pressing it again kills the program.
+Sending SIGUSR1 prints a progress report right away.
+
 Options:
+  -a, --assume <FILE>              Solve with the options in <FILE> forced
+  -b, --batch <DELIMITER>          Solve every instance between <DELIMITER>
+                                   lines
+  -B, --branch-counts <LEVELS>     Count solutions and nodes under every option
+                                   of the first <LEVELS> levels
+  -c, --choice-depth <LEVEL>       Trace the search down to <LEVEL> with -vv
+  -C, --verify <FILE>              Check the solutions in <FILE> and exit
+  -d, --decode <FILE>              Print the solution in a SAT model <FILE>
+  -D, --tree-depth <LEVEL>         Record the search tree up to <LEVEL>
+  -e, --cnf-encoding <ENCODING>    Encode at-most-one constraints in CNF as
+                                   pairwise, sequential or commander
+  -E, --engine <ENGINE>            Search with auto, dlx, bitset or cells
+  -F, --format <FORMAT>            Print solutions as text, json or ndjson
+  -I, --input <FORMAT>             Read the instance as text, json or matrix
+  -x, --export <FORMAT>            Print the instance as dimacs, opb or lp and
+                                   exit
   -f, --show-first                 Print first solution if it exists
+  -g, --tree-format <FORMAT>       Write the search tree as dot or json
   -h, --help                       Print this help menu
   -i, --solution-interval <SPACE>  Print a solution in intervals of <SPACE>
+  -j, --jobs <N>                   Solve batch instances with <N> threads
+  -k, --check-links                Check the links at every step of the search
+  -L, --load <FILE>                Read the instance from snapshot <FILE>
   -l, --level-limit <LEVEL>        Show up to <LEVEL> braches in reports
+  -M, --max-solutions <N>          Stop after finding <N> solutions
+  -N, --max-nodes <N>              Stop after visiting <N> nodes
+  -n, --tree-nodes <N>             Record up to <N> nodes of the search tree
+  -P, --profile                    Print node counts per level and item
   -r, --report <SECS>              Print a report every <SECS> seconds
+  -R, --report-nodes <N>           Print a report every <N> nodes instead
+  -S, --save <FILE>                Save a snapshot of the instance to <FILE>
   -s, --randomize <SEED>           Pick item to cover in a random fashion
   -t, --timeout <SECS>             Stop program execution after <SECS> seconds
-  -v, --verbose                    Print verbose output
+  -T, --tree <FILE>                Write the search tree to <FILE>
+  -u, --unique                     Stop at a second solution and tell whether
+                                   there are none, one or many
+  -U, --max-updates <N>            Stop after doing <N> updates
+  -v, --verbose                    Print verbose output, more if repeated
+  -V, --verbosity <LEVEL>          Set how verbose the output is, up to 4
 
 Default options:
+  -a: None,
+  -b: None,
+  -B: None,
+  -c: 10,
+  -C: None,
+  -d: None,
+  -D: None,
+  -e: sequential,
+  -E: auto (bitset for at most 128 primary items and few options, else dlx),
+  -F: text,
+  -I: text,
+  -x: None,
   -f: false (does not mean first solution is not printed, if -i=1 it will),
+  -g: dot,
   -h: false,
   -i: 0 (no solutions are printed by default),
+  -j: 1,
+  -k: false,
+  -L: None,
   -l: 12,
+  -M: None,
+  -N: None,
+  -n: 10000,
+  -P: false,
   -r: 5,
+  -R: None,
+  -S: None,
   -s: None (first item of minimum length is chosen),
   -t: None,
-  -v: false."
+  -T: None,
+  -u: false,
+  -U: None,
+  -v: 0 (same as -V 0)."
         );
     }
 }
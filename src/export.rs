@@ -0,0 +1,376 @@
+use std::io::{self, BufRead, Write};
+
+use crate::DancingLinks;
+use crate::config::CnfEncoding;
+use crate::input::Instance;
+use crate::verify::Verifier;
+
+const COMMANDER_GROUP_SIZE: usize = 3;
+const LP_TERMS_PER_LINE: usize = 8;
+
+struct Cnf {
+    variables: usize,
+    clauses: Vec<Vec<isize>>,
+}
+
+impl Cnf {
+    fn new_variable(&mut self) -> isize {
+        self.variables += 1;
+
+        self.variables.try_into().unwrap()
+    }
+
+    fn at_most_one(&mut self, x: &[isize], encoding: CnfEncoding) {
+        match encoding {
+            CnfEncoding::Pairwise => self.pairwise(x),
+            CnfEncoding::Sequential => self.sequential_counter(x),
+            CnfEncoding::Commander => self.commander(x),
+        }
+    }
+
+    fn pairwise(&mut self, x: &[isize]) {
+        for (k, &a) in x.iter().enumerate() {
+            for &b in &x[k + 1..] {
+                self.clauses.push(vec![-a, -b]);
+            }
+        }
+    }
+
+    // Sinz's sequential counter: s[k] holds iff some of x[0..=k] is true.
+    fn sequential_counter(&mut self, x: &[isize]) {
+        if x.len() < 2 {
+            return;
+        }
+
+        let s: Vec<isize> = (0..x.len() - 1).map(|_| self.new_variable()).collect();
+
+        self.clauses.push(vec![-x[0], s[0]]);
+
+        for k in 1..x.len() - 1 {
+            self.clauses.push(vec![-x[k], s[k]]);
+            self.clauses.push(vec![-s[k - 1], s[k]]);
+            self.clauses.push(vec![-x[k], -s[k - 1]]);
+        }
+
+        self.clauses.push(vec![-x[x.len() - 1], -s[x.len() - 2]]);
+    }
+
+    // Klieber and Kwon's commander encoding: each group of variables gets a
+    // commander that is true iff one of them is, then at most one commander
+    // is allowed recursively.
+    fn commander(&mut self, x: &[isize]) {
+        if x.len() <= COMMANDER_GROUP_SIZE {
+            self.pairwise(x);
+
+            return;
+        }
+
+        let mut commanders = Vec::new();
+
+        for group in x.chunks(COMMANDER_GROUP_SIZE) {
+            let c = self.new_variable();
+
+            self.pairwise(group);
+
+            let mut some = vec![-c];
+
+            for &a in group {
+                self.clauses.push(vec![-a, c]);
+                some.push(a);
+            }
+
+            self.clauses.push(some);
+            commanders.push(c);
+        }
+
+        self.commander(&commanders);
+    }
+}
+
+fn get_item_options(dlx: &DancingLinks, options: &[Vec<usize>]) -> Vec<Vec<isize>> {
+    let mut item_options = vec![Vec::new(); dlx.get_item_count() + 1];
+
+    for (k, option) in options.iter().enumerate() {
+        for &i in option {
+            item_options[i].push((k + 1).try_into().unwrap());
+        }
+    }
+
+    item_options
+}
+
+fn get_costs(dlx: &DancingLinks) -> Option<Vec<i64>> {
+    let costs: Vec<Option<i64>> = (1..=dlx.get_option_count())
+        .map(|k| dlx.get_option_data(k).cost)
+        .collect();
+
+    if costs.iter().all(|c| c.is_none()) {
+        None
+    } else {
+        Some(costs.into_iter().map(|c| c.unwrap_or(0)).collect())
+    }
+}
+
+pub fn write_dimacs<W: Write>(
+    dlx: &DancingLinks,
+    encoding: CnfEncoding,
+    mut writer: W,
+) -> io::Result<()> {
+    let options = dlx.get_option_items();
+    let item_options = get_item_options(dlx, &options);
+
+    let mut cnf = Cnf {
+        variables: options.len(),
+        clauses: Vec::new(),
+    };
+
+    for (i, x) in item_options.iter().enumerate().skip(1) {
+        if i <= dlx.get_primary() {
+            cnf.clauses.push(x.clone());
+        }
+
+        cnf.at_most_one(x, encoding);
+    }
+
+    writeln!(writer, "c Exact cover instance exported by dlx.")?;
+    writeln!(
+        writer,
+        "c Variables 1 to {} stand for the options, in input order.",
+        options.len(),
+    )?;
+
+    for (k, option) in options.iter().enumerate() {
+        let names: Vec<&str> = option.iter().map(|&i| dlx.get_item_name(i)).collect();
+
+        writeln!(writer, "c {} {}", k + 1, names.join(" "))?;
+    }
+
+    writeln!(writer, "p cnf {} {}", cnf.variables, cnf.clauses.len())?;
+
+    for clause in &cnf.clauses {
+        for literal in clause {
+            write!(writer, "{} ", literal)?;
+        }
+
+        writeln!(writer, "0")?;
+    }
+
+    Ok(())
+}
+
+pub fn write_opb<W: Write>(dlx: &DancingLinks, mut writer: W) -> io::Result<()> {
+    let options = dlx.get_option_items();
+    let item_options = get_item_options(dlx, &options);
+
+    let constraints = item_options
+        .iter()
+        .enumerate()
+        .skip(1)
+        .filter(|(i, x)| !x.is_empty() || *i <= dlx.get_primary())
+        .count();
+
+    // A primary item in no option is written as 0 x1 = 1, so x1 is declared
+    // even if there are no options.
+    writeln!(
+        writer,
+        "* #variable= {} #constraint= {}",
+        options.len().max(1),
+        constraints,
+    )?;
+    writeln!(writer, "* Exact cover instance exported by dlx.")?;
+
+    let objective: Vec<(i64, usize)> = get_costs(dlx)
+        .unwrap_or_default()
+        .into_iter()
+        .enumerate()
+        .filter(|&(_, cost)| cost != 0)
+        .map(|(k, cost)| (cost, k + 1))
+        .collect();
+
+    if !objective.is_empty() {
+        write!(writer, "min:")?;
+
+        for (cost, k) in objective {
+            write!(writer, " {:+} x{}", cost, k)?;
+        }
+
+        writeln!(writer, " ;")?;
+    }
+
+    // Secondary items are written as -x - y >= -1 since not every PB solver
+    // accepts <= constraints.
+    for (i, x) in item_options.iter().enumerate().skip(1) {
+        let primary = i <= dlx.get_primary();
+
+        writeln!(writer, "* {}", dlx.get_item_name(i))?;
+
+        if x.is_empty() {
+            if primary {
+                writeln!(writer, "0 x1 = 1 ;")?;
+            }
+
+            continue;
+        }
+
+        for k in x {
+            write!(writer, "{} x{} ", if primary { "+1" } else { "-1" }, k)?;
+        }
+
+        if primary {
+            writeln!(writer, "= 1 ;")?;
+        } else {
+            writeln!(writer, ">= -1 ;")?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn write_lp<W: Write>(dlx: &DancingLinks, mut writer: W) -> io::Result<()> {
+    let options = dlx.get_option_items();
+    let item_options = get_item_options(dlx, &options);
+
+    writeln!(writer, "\\ Exact cover instance exported by dlx.")?;
+    writeln!(writer, "Minimize")?;
+
+    let objective: Vec<(i64, usize)> = match get_costs(dlx) {
+        Some(costs) => costs
+            .into_iter()
+            .enumerate()
+            .filter(|&(_, cost)| cost != 0)
+            .map(|(k, cost)| (cost, k + 1))
+            .collect(),
+        None => Vec::new(),
+    };
+
+    write!(writer, " obj:")?;
+    write_lp_terms(&mut writer, &objective)?;
+    writeln!(writer)?;
+
+    writeln!(writer, "Subject To")?;
+
+    // Item names may hold characters that are not allowed in LP names, so
+    // rows are named after item indices instead.
+    for (i, x) in item_options.iter().enumerate().skip(1) {
+        let primary = i <= dlx.get_primary();
+
+        if x.is_empty() && !primary {
+            continue;
+        }
+
+        writeln!(writer, "\\ {}", dlx.get_item_name(i))?;
+        write!(writer, " i{}:", i)?;
+
+        let terms: Vec<(i64, usize)> = x.iter().map(|&k| (1, k as usize)).collect();
+
+        write_lp_terms(&mut writer, &terms)?;
+
+        if primary {
+            writeln!(writer, " = 1")?;
+        } else {
+            writeln!(writer, " <= 1")?;
+        }
+    }
+
+    writeln!(writer, "Binary")?;
+
+    for k in 1..=options.len().max(1) {
+        write!(writer, " x{}", k)?;
+
+        if k % LP_TERMS_PER_LINE == 0 {
+            writeln!(writer)?;
+        }
+    }
+
+    writeln!(writer)?;
+    writeln!(writer, "End")?;
+
+    Ok(())
+}
+
+// CPLEX limits the length of a line, so long sums are wrapped.
+fn write_lp_terms<W: Write>(writer: &mut W, terms: &[(i64, usize)]) -> io::Result<()> {
+    if terms.is_empty() {
+        return write!(writer, " 0 x1");
+    }
+
+    for (n, &(coefficient, k)) in terms.iter().enumerate() {
+        if n > 0 && n % LP_TERMS_PER_LINE == 0 {
+            writeln!(writer)?;
+        }
+
+        if n == 0 {
+            write!(writer, " {} x{}", coefficient, k)?;
+        } else if coefficient < 0 {
+            write!(writer, " - {} x{}", -coefficient, k)?;
+        } else {
+            write!(writer, " + {} x{}", coefficient, k)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a model as printed by SAT solvers, either in competition format
+/// (`s` and `v` lines) or as bare literals, and returns the chosen options.
+/// A model whose options are not an exact cover of the instance, as from a
+/// solver run on another encoding, is an error.
+pub fn decode_model<R: BufRead>(dlx: &DancingLinks, reader: R) -> Result<Vec<usize>, String> {
+    let option_count = dlx.get_option_count();
+
+    let mut options = Vec::new();
+
+    for line in reader.lines() {
+        let line = line.map_err(|err| err.to_string())?;
+        let line = line.trim();
+
+        let literals = match line.chars().next() {
+            Some('c') | None => continue,
+            Some('s') => {
+                if line.contains("UNSAT") {
+                    return Err(String::from(
+                        "The model file reports an unsatisfiable instance.",
+                    ));
+                }
+
+                continue;
+            }
+            Some('v') => &line[1..],
+            _ => line,
+        };
+
+        for literal in literals.split_whitespace() {
+            let literal: isize = literal
+                .parse()
+                .map_err(|_| format!("Invalid literal \"{literal}\" in model."))?;
+
+            if literal > 0 && literal as usize <= option_count {
+                options.push(literal as usize);
+            }
+        }
+    }
+
+    let option_items = dlx.get_option_items();
+
+    let names: Vec<Vec<String>> = options
+        .iter()
+        .map(|&k| {
+            option_items[k - 1]
+                .iter()
+                .map(|&i| dlx.get_item_name(i).to_string())
+                .collect()
+        })
+        .collect();
+
+    let instance = Instance::from_links(dlx);
+    let violations = Verifier::new(&instance).check(&names);
+
+    if !violations.is_empty() {
+        return Err(format!(
+            "The model is not an exact cover, numbering its options in the order given: {}",
+            violations.join(" "),
+        ));
+    }
+
+    Ok(options)
+}
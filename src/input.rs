@@ -0,0 +1,555 @@
+use std::collections::HashMap;
+use std::io::{BufRead, Read};
+use std::time::Instant;
+use std::{mem, str};
+
+use serde_json::Value;
+
+use crate::DancingLinks;
+use crate::config::{Config, InputFormat};
+use crate::utils::{FnvBuildHasher, OptionData};
+
+/// An instance of a batch, as the text that follows its delimiter line.
+pub struct BatchInstance {
+    pub name: Option<String>,
+    pub text: Vec<u8>,
+}
+
+/// Splits a stream into the instances that follow every line starting with
+/// `delimiter`, the rest of that line being the instance name. Text before the
+/// first delimiter is an unnamed instance unless it is blank. Instances are
+/// read one at a time, as the iterator is advanced.
+pub fn read_batch<R: BufRead>(reader: R, delimiter: &str) -> BatchReader<R> {
+    BatchReader {
+        reader,
+        delimiter: String::from(delimiter),
+        current: Some(BatchInstance {
+            name: None,
+            text: Vec::new(),
+        }),
+    }
+}
+
+/// Iterator over the instances of a batch, returned by `read_batch`.
+pub struct BatchReader<R> {
+    reader: R,
+    delimiter: String,
+    // The instance being read, or `None` once the stream is exhausted.
+    current: Option<BatchInstance>,
+}
+
+impl<R: BufRead> Iterator for BatchReader<R> {
+    type Item = BatchInstance;
+
+    fn next(&mut self) -> Option<BatchInstance> {
+        let mut buffer = Vec::new();
+
+        loop {
+            let instance = self.current.as_mut()?;
+
+            buffer.clear();
+
+            let n = self
+                .reader
+                .read_until(b'\n', &mut buffer)
+                .expect("Failed to read line.");
+
+            let finished = if n == 0 {
+                self.current.take()
+            } else if let Some(rest) = buffer.strip_prefix(self.delimiter.as_bytes()) {
+                let name = String::from_utf8_lossy(rest).trim().to_string();
+
+                Some(mem::replace(
+                    instance,
+                    BatchInstance {
+                        name: (!name.is_empty()).then_some(name),
+                        text: Vec::new(),
+                    },
+                ))
+            } else {
+                instance.text.extend_from_slice(&buffer);
+
+                None
+            };
+
+            if let Some(instance) = finished
+                && (instance.name.is_some() || !instance.text.iter().all(u8::is_ascii_whitespace))
+            {
+                return Some(instance);
+            }
+        }
+    }
+}
+
+pub fn read_text<R: BufRead>(config: &Config, reader: R) -> DancingLinks {
+    parse_text(config, reader)
+}
+
+pub fn read_json<R: Read>(config: &Config, reader: R) -> DancingLinks {
+    parse_json(config, reader)
+}
+
+pub fn read_matrix<R: BufRead>(config: &Config, reader: R) -> DancingLinks {
+    parse_matrix(config, reader)
+}
+
+/// Reads an instance in the format chosen with `-I` as it is given, without
+/// building the links of a search.
+pub fn read_instance<R: BufRead>(config: &Config, reader: R) -> Instance {
+    match config.get_input_format() {
+        InputFormat::Text => parse_text(config, reader),
+        InputFormat::Json => parse_json(config, reader),
+        InputFormat::Matrix => parse_matrix(config, reader),
+    }
+}
+
+/// An instance as read, its options kept as lists of items apart from any
+/// search structure, for checks that must not rely on the search.
+pub struct Instance {
+    item_names: Vec<String>,
+    item_index: HashMap<String, usize, FnvBuildHasher>,
+    options: Vec<Vec<usize>>,
+    primary: usize,
+}
+
+impl Instance {
+    /// Takes the items and options of `dlx`, for checks on options that were
+    /// taken from it anyway.
+    pub fn from_links(dlx: &DancingLinks) -> Self {
+        let names: Vec<&str> = (1..=dlx.get_item_count())
+            .map(|i| dlx.get_item_name(i))
+            .collect();
+
+        let mut instance = <Instance as InstanceBuilder>::new(
+            &dlx.config,
+            &names[..dlx.get_primary()],
+            &names[dlx.get_primary()..],
+        );
+
+        instance.options = dlx.get_option_items();
+
+        instance
+    }
+
+    pub fn find_item(&self, name: &str) -> Option<usize> {
+        self.item_index.get(name).copied()
+    }
+
+    pub fn get_item_name(&self, i: usize) -> &str {
+        &self.item_names[i]
+    }
+
+    pub fn get_primary(&self) -> usize {
+        self.primary
+    }
+
+    pub fn get_item_count(&self) -> usize {
+        self.item_names.len() - 1
+    }
+
+    /// Returns the items of every option, in the order they were given.
+    pub fn get_option_items(&self) -> &[Vec<usize>] {
+        &self.options
+    }
+}
+
+// What the readers fill with the items and options they read, so that the
+// same parsers build both the links of a search and an `Instance`.
+trait InstanceBuilder {
+    fn new(config: &Config, primary_items: &[&str], secondary_items: &[&str]) -> Self;
+    fn find_item(&self, name: &str) -> Option<usize>;
+    fn get_item_name(&self, i: usize) -> &str;
+    fn get_item_count(&self) -> usize;
+    fn add_option_items(&mut self, items: &[usize], data: OptionData);
+}
+
+impl InstanceBuilder for DancingLinks {
+    fn new(config: &Config, primary_items: &[&str], secondary_items: &[&str]) -> Self {
+        DancingLinks::new(config, primary_items, secondary_items)
+    }
+
+    fn find_item(&self, name: &str) -> Option<usize> {
+        DancingLinks::find_item(self, name)
+    }
+
+    fn get_item_name(&self, i: usize) -> &str {
+        DancingLinks::get_item_name(self, i)
+    }
+
+    fn get_item_count(&self) -> usize {
+        DancingLinks::get_item_count(self)
+    }
+
+    fn add_option_items(&mut self, items: &[usize], data: OptionData) {
+        DancingLinks::add_option_items(self, items, data);
+    }
+}
+
+impl InstanceBuilder for Instance {
+    fn new(_: &Config, primary_items: &[&str], secondary_items: &[&str]) -> Self {
+        let mut instance = Instance {
+            item_names: vec![String::new()],
+            item_index: HashMap::default(),
+            options: Vec::new(),
+            primary: primary_items.len(),
+        };
+
+        for item in primary_items.iter().chain(secondary_items) {
+            if instance.item_index.contains_key(*item) {
+                panic!("Item names must be unique.");
+            }
+
+            instance
+                .item_index
+                .insert(String::from(*item), instance.item_names.len());
+            instance.item_names.push(String::from(*item));
+        }
+
+        instance
+    }
+
+    fn find_item(&self, name: &str) -> Option<usize> {
+        Instance::find_item(self, name)
+    }
+
+    fn get_item_name(&self, i: usize) -> &str {
+        Instance::get_item_name(self, i)
+    }
+
+    fn get_item_count(&self) -> usize {
+        Instance::get_item_count(self)
+    }
+
+    fn add_option_items(&mut self, items: &[usize], _: OptionData) {
+        if items.iter().any(|&i| i == 0 || i > self.get_item_count()) {
+            panic!("Options must contain known items.");
+        }
+
+        let mut sorted = items.to_vec();
+        sorted.sort_unstable();
+
+        if sorted.windows(2).any(|w| w[0] == w[1]) {
+            panic!("Options must contain unique items.");
+        }
+
+        self.options.push(items.to_vec());
+    }
+}
+
+fn parse_text<B: InstanceBuilder, R: BufRead>(config: &Config, mut reader: R) -> B {
+    let now = Instant::now();
+
+    let mut buffer = Vec::new();
+    let mut line = 0;
+    let mut read_bytes = 0;
+    let mut nodes = 0;
+
+    let mut dlx = loop {
+        buffer.clear();
+
+        let n = reader
+            .read_until(b'\n', &mut buffer)
+            .expect("Failed to read line.");
+
+        if n == 0 {
+            panic!("Primary items are required.");
+        }
+
+        line += 1;
+        read_bytes += n;
+
+        if buffer.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+
+        if !buffer.is_ascii() {
+            panic!("Item names should belong to ASCII range.");
+        }
+
+        let item_line = str::from_utf8(&buffer).unwrap();
+
+        if item_line.matches('|').count() > 1 {
+            panic!("Item type separator \'|\' can only appear once.");
+        }
+
+        let mut items = item_line.split('|');
+
+        let primary: Vec<&str> = items.next().unwrap().split_whitespace().collect();
+
+        let secondary: Vec<&str> = match items.next() {
+            Some(s) => s.split_whitespace().collect(),
+            None => Vec::new(),
+        };
+
+        if primary.is_empty() {
+            panic!("Primary items are required.");
+        }
+
+        break B::new(config, &primary, &secondary);
+    };
+
+    // Tokens are looked up as bytes, since item names were checked to be ASCII
+    // when they were read.
+    let item_index: HashMap<Box<[u8]>, usize, FnvBuildHasher> = (1..=dlx.get_item_count())
+        .map(|i| (Box::from(dlx.get_item_name(i).as_bytes()), i))
+        .collect();
+
+    let mut option = Vec::new();
+
+    loop {
+        buffer.clear();
+
+        let n = reader
+            .read_until(b'\n', &mut buffer)
+            .expect("Failed to read line.");
+
+        if n == 0 {
+            break;
+        }
+
+        line += 1;
+        read_bytes += n;
+
+        option.clear();
+
+        for token in buffer.split(u8::is_ascii_whitespace) {
+            if token.is_empty() {
+                continue;
+            }
+
+            match item_index.get(token) {
+                Some(&i) => option.push(i),
+                None => panic!("Options must contain known items."),
+            }
+        }
+
+        if option.is_empty() {
+            continue;
+        }
+
+        nodes += option.len();
+
+        dlx.add_option_items(
+            &option,
+            OptionData {
+                line: Some(line),
+                ..Default::default()
+            },
+        );
+    }
+
+    if config.is_verbose() {
+        let elapsed = now.elapsed();
+
+        eprintln!(
+            "Parsed {} bytes and {} nodes in {:.5?} ({:.2} MB/s, {:.0} nodes/s).",
+            read_bytes,
+            nodes,
+            elapsed,
+            read_bytes as f64 / 1e6 / elapsed.as_secs_f64(),
+            nodes as f64 / elapsed.as_secs_f64(),
+        );
+    }
+
+    dlx
+}
+
+fn parse_json<B: InstanceBuilder, R: Read>(config: &Config, reader: R) -> B {
+    let instance: Value = serde_json::from_reader(reader).unwrap_or_else(|err| {
+        panic!("Failed to parse JSON instance: {}", err);
+    });
+
+    let primary_items = get_item_names(&instance, "primary");
+    let secondary_items = get_item_names(&instance, "secondary");
+
+    if primary_items.is_empty() {
+        panic!("Primary items are required.");
+    }
+
+    let mut dlx = B::new(config, &primary_items, &secondary_items);
+
+    let options = match instance.get("options") {
+        Some(Value::Array(options)) => options,
+        Some(_) => panic!("Field \"options\" must be a list."),
+        None => return dlx,
+    };
+
+    for (k, option) in options.iter().enumerate() {
+        let (items, label, payload, cost) = match option {
+            Value::Array(items) => (items, None, None, None),
+            Value::Object(fields) => {
+                let items = match fields.get("items") {
+                    Some(Value::Array(items)) => items,
+                    _ => panic!("Option {} must have a list of \"items\".", k + 1),
+                };
+
+                let label = match fields.get("label") {
+                    Some(Value::String(label)) => Some(label.clone()),
+                    Some(Value::Null) | None => None,
+                    Some(_) => panic!("Label of option {} must be a string.", k + 1),
+                };
+
+                let cost = match fields.get("cost") {
+                    Some(Value::Null) | None => None,
+                    Some(cost) => match cost.as_i64() {
+                        Some(cost) => Some(cost),
+                        None => panic!("Cost of option {} must be an integer.", k + 1),
+                    },
+                };
+
+                (items, label, fields.get("payload").cloned(), cost)
+            }
+            _ => panic!("Option {} must be a list or an object.", k + 1),
+        };
+
+        let option: Vec<usize> = items
+            .iter()
+            .map(
+                |item| match item.as_str().and_then(|name| dlx.find_item(name)) {
+                    Some(i) => i,
+                    None => panic!("Options must contain known items."),
+                },
+            )
+            .collect();
+
+        dlx.add_option_items(
+            &option,
+            OptionData {
+                line: None,
+                label,
+                payload,
+                cost,
+            },
+        );
+    }
+
+    dlx
+}
+
+fn parse_matrix<B: InstanceBuilder, R: BufRead>(config: &Config, reader: R) -> B {
+    let mut lines = reader
+        .lines()
+        .map(|line| line.expect("Failed to read line."))
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'));
+
+    let header = match lines.next() {
+        Some((_, header)) => header,
+        None => panic!("Matrix header with the number of columns is required."),
+    };
+
+    let header: Vec<usize> = header
+        .split_whitespace()
+        .map(|n| {
+            n.parse()
+                .expect("Matrix header must hold the column count and optional secondary count.")
+        })
+        .collect();
+
+    let (columns, secondary) = match header[..] {
+        [columns] => (columns, 0),
+        [columns, secondary] => (columns, secondary),
+        _ => panic!("Matrix header must hold the column count and optional secondary count."),
+    };
+
+    if secondary >= columns {
+        panic!("Primary items are required.");
+    }
+
+    let names: Vec<String> = (1..=columns).map(|j| format!("c{}", j)).collect();
+    let names: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+
+    let primary = columns - secondary;
+
+    let mut dlx = B::new(config, &names[..primary], &names[primary..]);
+
+    for (k, row) in lines {
+        let tokens: Vec<&str> = row.split_whitespace().collect();
+
+        let dense = tokens.len() == columns && tokens.iter().all(|&t| t == "0" || t == "1");
+
+        let option: Vec<usize> = if dense {
+            tokens
+                .iter()
+                .enumerate()
+                .filter(|&(_, &t)| t == "1")
+                .map(|(j, _)| j + 1)
+                .collect()
+        } else {
+            tokens
+                .iter()
+                .map(|t| match t.parse::<usize>() {
+                    Ok(j) if (1..=columns).contains(&j) => j,
+                    _ => panic!("Row on line {} has an invalid column index {}.", k + 1, t),
+                })
+                .collect()
+        };
+
+        if option.is_empty() {
+            panic!("Row on line {} does not cover any column.", k + 1);
+        }
+
+        dlx.add_option_items(
+            &option,
+            OptionData {
+                line: Some(k + 1),
+                ..Default::default()
+            },
+        );
+    }
+
+    dlx
+}
+
+/// Reads options given by their item names, one per line, and returns their
+/// numbers in `dlx`. Blank lines and lines starting with '#' are skipped.
+pub fn read_assumptions<R: BufRead>(dlx: &DancingLinks, reader: R) -> Result<Vec<usize>, String> {
+    let mut options = Vec::new();
+
+    for (k, line) in reader.lines().enumerate() {
+        let line = line.map_err(|err| err.to_string())?;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let items = line
+            .split_whitespace()
+            .map(|name| {
+                dlx.find_item(name)
+                    .ok_or_else(|| format!("Unknown item \"{name}\" on line {}.", k + 1))
+            })
+            .collect::<Result<Vec<usize>, String>>()?;
+
+        match dlx.find_option(&items) {
+            Some(option) => options.push(option),
+            None => return Err(format!("Line {} is not an option of the instance.", k + 1)),
+        }
+    }
+
+    Ok(options)
+}
+
+fn get_item_names<'a>(instance: &'a Value, field: &str) -> Vec<&'a str> {
+    let items = match instance.get(field) {
+        Some(Value::Array(items)) => items,
+        Some(_) => panic!("Field \"{field}\" must be a list of item names."),
+        None => return Vec::new(),
+    };
+
+    items
+        .iter()
+        .map(|item| match item {
+            Value::String(name) if is_valid_name(name) => name.as_str(),
+            _ => panic!("Item names must be non-empty ASCII strings without whitespace or '|'."),
+        })
+        .collect()
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.is_ascii()
+        && !name.contains('|')
+        && !name.contains(|c: char| c.is_ascii_whitespace())
+}
@@ -0,0 +1,500 @@
+use std::collections::HashMap;
+
+use crate::Statistics;
+use crate::config::Config;
+use crate::monitor::{Counts, Monitor, StopReason};
+use crate::output::{self, ChosenOption};
+use crate::profile::Profile;
+use crate::utils::{FnvBuildHasher, OptionData};
+
+/// Exact cover instance whose options are produced on demand.
+///
+/// `generator` is called with the index of an item, as returned by
+/// `find_item`, and returns every option containing it as item indices plus
+/// its data. It is called at most once per item, and options generated for
+/// several items are only stored once: the k-th option with a given set of
+/// items returned for one item is the k-th one returned for any other, so a
+/// repeated option must be returned as many times for each of its items.
+///
+/// Since the degree of an item is unknown until its options are generated,
+/// every node generates the first uncovered primary item in input order if
+/// needed, then branches on the generated uncovered primary item with fewest
+/// live options, that is, options with no covered item. Items should thus be
+/// given in the order they are best filled, like the cells of a board in
+/// reading order for packings.
+pub struct LazyDancingLinks<F> {
+    generator: F,
+    item_names: Vec<String>,
+    item_index: HashMap<String, usize, FnvBuildHasher>,
+    item_options: Vec<Option<Vec<usize>>>,
+    option_items: Vec<Vec<usize>>,
+    option_data: Vec<OptionData>,
+    // Stored options containing every item, generated for it or not.
+    item_occurrences: Vec<Vec<usize>>,
+    // Stored options by their sorted items, in the order they were generated.
+    option_index: HashMap<Vec<usize>, Vec<usize>, FnvBuildHasher>,
+    primary: usize,
+    secondary: usize,
+    config: Config,
+}
+
+// Search state: the covered items, the number of covered items in every
+// stored option, the number of live options of every item among the stored
+// ones, and the number of uncovered primary items.
+struct Coverage {
+    covered: Vec<bool>,
+    clashes: Vec<usize>,
+    live: Vec<usize>,
+    remaining: usize,
+}
+
+impl<F> LazyDancingLinks<F>
+where
+    F: FnMut(usize) -> Vec<(Vec<usize>, OptionData)>,
+{
+    pub fn new(
+        config: &Config,
+        primary_items: &[&str],
+        secondary_items: &[&str],
+        generator: F,
+    ) -> Self {
+        let n1 = primary_items.len();
+        let n2 = secondary_items.len();
+        let n = n1 + n2;
+
+        let mut ldlx = LazyDancingLinks {
+            generator,
+            item_names: Vec::with_capacity(n + 1),
+            item_index: HashMap::with_capacity_and_hasher(n, FnvBuildHasher::default()),
+            item_options: vec![None; n + 1],
+            option_items: Vec::new(),
+            option_data: Vec::new(),
+            item_occurrences: vec![Vec::new(); n + 1],
+            option_index: HashMap::default(),
+            primary: n1,
+            secondary: n2,
+            config: config.clone(),
+        };
+
+        ldlx.item_names.push(String::new());
+
+        for item in primary_items.iter().chain(secondary_items) {
+            if ldlx.item_index.contains_key(*item) {
+                panic!("Item names must be unique.");
+            }
+
+            ldlx.item_index
+                .insert(String::from(*item), ldlx.item_names.len());
+            ldlx.item_names.push(String::from(*item));
+        }
+
+        ldlx
+    }
+
+    pub fn dance(&mut self) -> Statistics {
+        let mut monitor = Monitor::new(&self.config);
+
+        let depth = self.primary + 1;
+
+        let mut state = Coverage {
+            covered: vec![false; self.get_item_count() + 1],
+            clashes: vec![0; self.option_items.len()],
+            live: self.item_occurrences.iter().map(Vec::len).collect(),
+            remaining: self.primary,
+        };
+
+        // Primary items whose options are known, among which the search
+        // branches.
+        let mut generated: Vec<usize> = (1..=self.primary)
+            .filter(|&i| self.item_options[i].is_some())
+            .collect();
+
+        // Options of the branching item of every level that do not clash with
+        // the ones already chosen, stacked one level after another.
+        let mut candidates = Vec::new();
+
+        // The first uncovered primary item of every level, since items covered
+        // at a level stay covered below it.
+        let mut first = vec![0; depth];
+        let mut offset = vec![0; depth];
+        let mut item = vec![0; depth];
+        let mut position = vec![0; depth];
+        let mut length = vec![0; depth];
+
+        let mut level = 0;
+        let mut enter = true;
+
+        let mut counts = Counts::default();
+
+        let mut profile = self
+            .config
+            .is_profiling()
+            .then(|| Profile::new(&self.item_names));
+
+        let show_first = self.config.show_first();
+        let solution_interval = self.config.get_solution_interval();
+        let solution_limit = self.config.get_solution_limit();
+        let format = self.config.get_format();
+        let mut printed_solutions = 0;
+
+        // The deepest partial solution so far that is not a solution, shown if the
+        // search is stopped.
+        let mut deepest = Vec::new();
+        let mut deepest_uncovered: Vec<usize> = (1..=self.primary).collect();
+
+        loop {
+            let stop = monitor.check(
+                &counts,
+                None,
+                enter,
+                || {
+                    if enter {
+                        state.remaining > 0
+                    } else {
+                        position[level] < length[level]
+                    }
+                },
+                || (0..level).map(|l| (Some(position[l]), length[l])).collect(),
+            );
+
+            if let Some(stop) = stop {
+                let partial = output::Partial {
+                    options: (0..level)
+                        .map(|l| {
+                            let o = candidates[offset[l] + position[l] - 1];
+
+                            self.get_chosen_option(o, item[l], position[l], length[l])
+                        })
+                        .collect(),
+                    explored: stop.explored,
+                    deepest: deepest
+                        .iter()
+                        .map(|&(o, i, k, length)| self.get_chosen_option(o, i, k, length))
+                        .collect(),
+                    uncovered: deepest_uncovered
+                        .iter()
+                        .map(|&i| self.item_names[i].as_str())
+                        .collect(),
+                };
+
+                output::print_partial(format, &partial);
+
+                return Statistics {
+                    profile,
+                    ..Statistics::stopped(counts, monitor.elapsed(), stop.reason)
+                };
+            }
+
+            if enter {
+                enter = false;
+                counts.nodes += 1;
+
+                if state.remaining == 0 {
+                    if counts.max_level < level + 1 {
+                        counts.max_level = level + 1;
+                    }
+
+                    counts.solutions += 1;
+
+                    if let Some(profile) = &mut profile {
+                        profile.add_solution(level);
+                    }
+
+                    if (show_first && counts.solutions == 1)
+                        || (solution_interval > 0 && counts.solutions % solution_interval == 0)
+                    {
+                        let options: Vec<ChosenOption> = (0..level)
+                            .map(|l| {
+                                let o = candidates[offset[l] + position[l] - 1];
+
+                                self.get_chosen_option(o, item[l], position[l], length[l])
+                            })
+                            .collect();
+
+                        output::print_solution(
+                            format,
+                            counts.solutions,
+                            &options,
+                            printed_solutions == 0,
+                        );
+
+                        printed_solutions += 1;
+                    }
+
+                    if solution_limit == Some(counts.solutions) {
+                        return Statistics {
+                            profile,
+                            ..Statistics::stopped(
+                                counts,
+                                monitor.elapsed(),
+                                StopReason::LimitReached,
+                            )
+                        };
+                    }
+
+                    if level == 0 {
+                        break;
+                    }
+
+                    level -= 1;
+
+                    let o = candidates[offset[level] + position[level] - 1];
+                    self.release(&mut state, o);
+
+                    continue;
+                }
+
+                let mut f = if level == 0 { 1 } else { first[level - 1] };
+
+                while state.covered[f] {
+                    f += 1;
+                }
+
+                first[level] = f;
+
+                if self.item_options[f].is_none() {
+                    self.generate(f, &mut state);
+                    generated.push(f);
+                }
+
+                let mut i = f;
+
+                for &j in &generated {
+                    if !state.covered[j] && (state.live[j], j) < (state.live[i], i) {
+                        i = j;
+                    }
+                }
+
+                offset[level] = candidates.len();
+
+                for &o in self.item_options[i].as_ref().unwrap() {
+                    if state.clashes[o] == 0 {
+                        candidates.push(o);
+                    }
+                }
+
+                item[level] = i;
+                position[level] = 0;
+                length[level] = candidates.len() - offset[level];
+
+                if counts.max_degree < length[level] {
+                    counts.max_degree = length[level];
+                }
+
+                if let Some(profile) = &mut profile {
+                    profile.add_branch(level, i, length[level]);
+                }
+            }
+
+            if position[level] == length[level] {
+                candidates.truncate(offset[level]);
+
+                if level == 0 {
+                    break;
+                }
+
+                level -= 1;
+
+                let o = candidates[offset[level] + position[level] - 1];
+                self.release(&mut state, o);
+
+                continue;
+            }
+
+            let o = candidates[offset[level] + position[level]];
+
+            position[level] += 1;
+
+            counts.updates += self.cover(&mut state, o);
+
+            level += 1;
+            enter = true;
+
+            if counts.max_level < level {
+                counts.max_level = level;
+            }
+
+            if state.remaining > 0 && deepest.len() < level {
+                deepest = (0..level)
+                    .map(|l| {
+                        let o = candidates[offset[l] + position[l] - 1];
+
+                        (o, item[l], position[l], length[l])
+                    })
+                    .collect();
+                deepest_uncovered = (1..=self.primary).filter(|&i| !state.covered[i]).collect();
+            }
+        }
+
+        Statistics {
+            profile,
+            ..Statistics::finished(counts, monitor.elapsed())
+        }
+    }
+
+    // Calls the generator for item `i`, counting the live options of the
+    // items of every new option.
+    fn generate(&mut self, i: usize, state: &mut Coverage) {
+        let mut options = Vec::new();
+
+        // Times every set of items was returned by this call so far.
+        let mut repeats: HashMap<Vec<usize>, usize, FnvBuildHasher> = HashMap::default();
+
+        for (items, data) in (self.generator)(i) {
+            let mut key = items.clone();
+            key.sort_unstable();
+
+            if key.iter().any(|&j| j == 0 || j > self.get_item_count()) {
+                panic!("Options must contain known items.");
+            }
+
+            if key.windows(2).any(|w| w[0] == w[1]) {
+                panic!("Options must contain unique items.");
+            }
+
+            if key.binary_search(&i).is_err() {
+                panic!("Generated options must contain the item they were generated for.");
+            }
+
+            let repeat = repeats.entry(key.clone()).or_default();
+            let k = *repeat;
+            *repeat += 1;
+
+            let stored = self.option_index.entry(key).or_default();
+
+            let o = match stored.get(k) {
+                Some(&o) => o,
+                None => {
+                    let o = self.option_items.len();
+
+                    let clashes = items.iter().filter(|&&j| state.covered[j]).count();
+
+                    for &j in &items {
+                        self.item_occurrences[j].push(o);
+
+                        if clashes == 0 {
+                            state.live[j] += 1;
+                        }
+                    }
+
+                    state.clashes.push(clashes);
+
+                    stored.push(o);
+                    self.option_items.push(items);
+                    self.option_data.push(data);
+
+                    o
+                }
+            };
+
+            options.push(o);
+        }
+
+        self.item_options[i] = Some(options);
+    }
+
+    // Covers the items of option `o`, which hides the options containing them
+    // from the live counts of their items. Returns the updates done: one per
+    // item covered and one per live count lowered, as `DancingLinks` counts
+    // one per node it unlinks.
+    fn cover(&self, state: &mut Coverage, o: usize) -> usize {
+        let mut updates = 0;
+
+        for &j in &self.option_items[o] {
+            state.covered[j] = true;
+
+            if j <= self.primary {
+                state.remaining -= 1;
+            }
+
+            updates += 1;
+
+            for &p in &self.item_occurrences[j] {
+                state.clashes[p] += 1;
+
+                if state.clashes[p] == 1 {
+                    for &k in &self.option_items[p] {
+                        state.live[k] -= 1;
+                    }
+
+                    updates += self.option_items[p].len();
+                }
+            }
+        }
+
+        updates
+    }
+
+    // Undoes `cover` for option `o`.
+    fn release(&self, state: &mut Coverage, o: usize) {
+        for &j in self.option_items[o].iter().rev() {
+            for &p in &self.item_occurrences[j] {
+                state.clashes[p] -= 1;
+
+                if state.clashes[p] == 0 {
+                    for &k in &self.option_items[p] {
+                        state.live[k] += 1;
+                    }
+                }
+            }
+
+            state.covered[j] = false;
+
+            if j <= self.primary {
+                state.remaining += 1;
+            }
+        }
+    }
+
+    fn get_chosen_option(
+        &self,
+        o: usize,
+        i: usize,
+        position: usize,
+        length: usize,
+    ) -> ChosenOption<'_> {
+        let items = &self.option_items[o];
+
+        ChosenOption {
+            option: o + 1,
+            items: items.iter().map(|&j| self.item_names[j].as_str()).collect(),
+            first: items.iter().position(|&j| j == i).unwrap(),
+            position: Some(position),
+            length,
+            data: &self.option_data[o],
+        }
+    }
+
+    pub fn find_item(&self, name: &str) -> Option<usize> {
+        self.item_index.get(name).copied()
+    }
+
+    pub fn get_item_name(&self, i: usize) -> &str {
+        &self.item_names[i]
+    }
+
+    pub fn get_primary(&self) -> usize {
+        self.primary
+    }
+
+    pub fn get_secondary(&self) -> usize {
+        self.secondary
+    }
+
+    pub fn get_item_count(&self) -> usize {
+        self.primary + self.secondary
+    }
+
+    /// Returns the number of options generated so far.
+    pub fn get_option_count(&self) -> usize {
+        self.option_items.len()
+    }
+
+    pub fn get_option_data(&self, option: usize) -> &OptionData {
+        &self.option_data[option - 1]
+    }
+
+    pub fn get_option_items(&self) -> Vec<Vec<usize>> {
+        self.option_items.clone()
+    }
+}
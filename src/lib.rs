@@ -1,19 +1,127 @@
+pub mod bitset;
+pub mod branches;
+pub mod cells;
 pub mod config;
-
+pub mod export;
+pub mod input;
+pub mod lazy;
+mod monitor;
+pub mod output;
+pub mod profile;
+pub mod snapshot;
+pub mod tree;
 mod utils;
+pub mod verify;
 
-use std::collections::{HashMap, HashSet};
-use std::time::{Duration, Instant};
+use std::collections::HashMap;
+use std::mem;
+use std::ops::Range;
+use std::time::Duration;
 
 use rand::prelude::*;
 
+use crate::branches::BranchTable;
 use crate::config::*;
+use crate::monitor::{Counts, Monitor, StopReason};
+use crate::output::ChosenOption;
+use crate::profile::Profile;
+use crate::tree::{NodeKind, SearchTree};
 use crate::utils::*;
 
+pub use crate::monitor::install_signal_handlers;
+pub use crate::utils::OptionData;
+
+pub struct Statistics {
+    pub solution_count: usize,
+    pub elapsed_time: Duration,
+    pub visited_nodes: usize,
+    pub update_count: usize,
+    pub max_degree: usize,
+    pub max_level: usize,
+    pub timed_out: bool,
+    /// Whether the search stopped at `--max-solutions`, `--max-nodes` or
+    /// `--max-updates`, or at the second solution of `--unique`.
+    pub limit_reached: bool,
+    /// Whether the search was stopped by SIGINT.
+    pub interrupted: bool,
+    /// Counts per level and per item, with `--profile`.
+    pub profile: Option<Profile>,
+    /// Memory accesses of the dlx engine, when built with the `mems` feature.
+    pub mems: Option<usize>,
+    /// Counts under every option of the first levels, with `--branch-counts`.
+    pub branches: Option<BranchTable>,
+}
+
+/// Whether an instance has no solution, exactly one or several, as reported
+/// by `--unique`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    None,
+    Unique,
+    Multiple,
+    Unknown,
+}
+
+impl Verdict {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Verdict::None => "none",
+            Verdict::Unique => "unique",
+            Verdict::Multiple => "multiple",
+            Verdict::Unknown => "unknown",
+        }
+    }
+}
+
+impl Statistics {
+    pub(crate) fn finished(counts: Counts, elapsed_time: Duration) -> Self {
+        Statistics {
+            solution_count: counts.solutions,
+            elapsed_time,
+            visited_nodes: counts.nodes,
+            update_count: counts.updates,
+            max_degree: counts.max_degree,
+            max_level: counts.max_level,
+            timed_out: false,
+            limit_reached: false,
+            interrupted: false,
+            profile: None,
+            mems: None,
+            branches: None,
+        }
+    }
+
+    pub(crate) fn stopped(counts: Counts, elapsed_time: Duration, reason: StopReason) -> Self {
+        Statistics {
+            timed_out: reason == StopReason::Timeout,
+            limit_reached: reason == StopReason::LimitReached,
+            interrupted: reason == StopReason::Interrupted,
+            ..Statistics::finished(counts, elapsed_time)
+        }
+    }
+
+    /// Tells how many solutions there are, as far as the search went. A
+    /// timeout before the second solution leaves it unknown.
+    pub fn verdict(&self) -> Verdict {
+        match self.solution_count {
+            n if n >= 2 => Verdict::Multiple,
+            _ if self.timed_out || self.limit_reached || self.interrupted => Verdict::Unknown,
+            1 => Verdict::Unique,
+            _ => Verdict::None,
+        }
+    }
+}
+
 pub struct DancingLinks {
-    item_header: Vec<Record>,
-    node_list: Vec<Node>,
-    item_index: HashMap<String, usize>,
+    item_header: ItemHeader,
+    node_list: NodeList,
+    item_names: Vec<String>,
+    option_data: Vec<OptionData>,
+    item_index: HashMap<String, usize, FnvBuildHasher>,
+    option_marks: Vec<u64>,
+    option_active: Vec<bool>,
+    item_active: Vec<bool>,
+    mems: MemCounter,
     primary: usize,
     secondary: usize,
     config: Config,
@@ -26,16 +134,23 @@ impl DancingLinks {
         let n = n1 + n2;
 
         let mut dlx = DancingLinks {
-            item_header: Vec::with_capacity(n + 2),
-            node_list: Vec::with_capacity(n + 2),
-            item_index: HashMap::new(),
+            item_header: ItemHeader::with_capacity(n + 2),
+            node_list: NodeList::with_capacity(n + 2),
+            item_names: Vec::with_capacity(n + 2),
+            option_data: Vec::new(),
+            item_index: HashMap::with_capacity_and_hasher(n, FnvBuildHasher::default()),
+            option_marks: vec![0; (n + 1).div_ceil(64)],
+            option_active: Vec::new(),
+            item_active: vec![true; n + 1],
+            mems: MemCounter::default(),
             primary: n1,
             secondary: n2,
             config: config.clone(),
         };
 
-        dlx.item_header.push(Record::new_unnamed(n1, 1));
-        dlx.node_list.push(Node::new_spacer());
+        dlx.item_header.push(n1, 1);
+        dlx.node_list.push_spacer();
+        dlx.item_names.push(String::new());
 
         for item in primary_items {
             dlx.add_item(item);
@@ -45,11 +160,12 @@ impl DancingLinks {
             dlx.add_item(item);
         }
 
-        dlx.item_header.push(Record::new_unnamed(n, n1 + 1));
-        dlx.node_list.push(Node::new_spacer());
+        dlx.item_header.push(n, n1 + 1);
+        dlx.node_list.push_spacer();
+        dlx.item_names.push(String::new());
 
-        dlx.item_header[n1].right = 0;
-        dlx.item_header[n1 + 1].left = n + 1;
+        dlx.set_right(n1, 0);
+        dlx.set_left(n1 + 1, n + 1);
 
         dlx
     }
@@ -61,43 +177,318 @@ impl DancingLinks {
 
         let i = self.get_list_len();
 
-        self.item_header.push(Record::new(item, i));
-        self.node_list.push(Node::new_header(i));
+        self.item_header.push(i - 1, i + 1);
+        self.node_list.push_header(i);
+        self.item_names.push(String::from(item));
         self.item_index.insert(String::from(item), i);
     }
 
-    pub fn add_option(&mut self, option_str: &str) {
-        let mut option_items = HashSet::new();
+    /// Appends an item after the primary or the secondary ones and returns its
+    /// index. Adding a primary item shifts the indices of the secondary ones,
+    /// while options keep their numbers.
+    pub fn append_item(&mut self, name: &str, primary: bool) -> usize {
+        if self.item_index.contains_key(name) {
+            panic!("Item names must be unique.");
+        }
 
-        let spacer = self.get_list_len() - 1;
+        let n1 = self.primary;
+        let shift = |i: usize| if primary && i > n1 { i + 1 } else { i };
 
-        for item_name in option_str.split_whitespace() {
-            if let Some(&i) = self.item_index.get(item_name) {
-                if option_items.contains(&i) {
-                    panic!("Options must contain unique items.");
-                }
+        let mut primary_items: Vec<&str> = (1..=n1).map(|i| self.get_item_name(i)).collect();
+        let mut secondary_items: Vec<&str> = (n1 + 1..=self.get_item_count())
+            .map(|i| self.get_item_name(i))
+            .collect();
 
-                let u = self.get_up(i);
-                let j = self.get_list_len();
+        if primary {
+            primary_items.push(name);
+        } else {
+            secondary_items.push(name);
+        }
 
-                self.add_node(i);
-                self.node_list.push(Node::new(i.try_into().unwrap(), u, i));
-                self.set_down(u, j);
-                self.set_up(i, j);
+        let mut dlx = DancingLinks::new(&self.config, &primary_items, &secondary_items);
 
-                option_items.insert(i);
-            } else {
+        for (k, items) in self.get_option_items().iter().enumerate() {
+            let items: Vec<usize> = items.iter().map(|&i| shift(i)).collect();
+
+            dlx.add_option_items(&items, self.option_data[k].clone());
+        }
+
+        dlx.option_active.clone_from(&self.option_active);
+
+        for (i, &active) in self.item_active.iter().enumerate() {
+            dlx.item_active[shift(i)] = active;
+        }
+
+        *self = dlx;
+
+        self.find_item(name).unwrap()
+    }
+
+    /// Adds an option and returns its number, which never changes.
+    pub fn add_option(&mut self, option_str: &str) -> usize {
+        self.add_option_with_data(option_str, OptionData::default())
+    }
+
+    pub fn add_option_with_data(&mut self, option_str: &str, data: OptionData) -> usize {
+        let items: Vec<usize> = option_str
+            .split_whitespace()
+            .map(|item_name| match self.find_item(item_name) {
+                Some(i) => i,
+                None => panic!("Options must contain known items."),
+            })
+            .collect();
+
+        self.add_option_items(&items, data)
+    }
+
+    /// Adds an option given by item indices, as returned by `find_item`.
+    pub fn add_option_items(&mut self, items: &[usize], data: OptionData) -> usize {
+        let spacer = self.get_list_len() - 1;
+
+        for &i in items {
+            if i == 0 || i > self.get_item_count() {
                 panic!("Options must contain known items.");
             }
+
+            let mark = 1 << (i % 64);
+
+            if self.option_marks[i / 64] & mark != 0 {
+                panic!("Options must contain unique items.");
+            }
+
+            self.option_marks[i / 64] |= mark;
+
+            let u = self.get_up(i);
+            let j = self.get_list_len();
+
+            self.add_node(i);
+            self.node_list.push(i as isize, u, i);
+            self.set_down(u, j);
+            self.set_up(i, j);
+        }
+
+        for &i in items {
+            self.option_marks[i / 64] = 0;
         }
 
-        self.node_list
-            .push(Node::new(self.get_top(spacer) - 1, spacer + 1, 0));
+        self.node_list.push(self.get_top(spacer) - 1, spacer + 1, 0);
         self.set_down(spacer, self.get_list_len() - 2);
+
+        self.option_data.push(data);
+        self.option_active.push(true);
+
+        self.option_data.len()
+    }
+
+    /// Leaves option `option` out of the instance until it is reactivated.
+    /// Deactivations only take effect while dancing, so they can be undone in
+    /// any order.
+    pub fn deactivate_option(&mut self, option: usize) {
+        self.option_active[option - 1] = false;
+    }
+
+    pub fn reactivate_option(&mut self, option: usize) {
+        self.option_active[option - 1] = true;
+    }
+
+    /// Leaves item `i` out of the instance, along with every option that
+    /// contains it, until it is reactivated.
+    pub fn deactivate_item(&mut self, i: usize) {
+        if i == 0 || i > self.get_item_count() {
+            panic!("Item {i} does not belong to the instance.");
+        }
+
+        self.item_active[i] = false;
+    }
+
+    pub fn reactivate_item(&mut self, i: usize) {
+        if i == 0 || i > self.get_item_count() {
+            panic!("Item {i} does not belong to the instance.");
+        }
+
+        self.item_active[i] = true;
+    }
+
+    pub fn is_option_active(&self, option: usize) -> bool {
+        self.option_active[option - 1]
+    }
+
+    pub fn is_item_active(&self, i: usize) -> bool {
+        self.item_active[i]
+    }
+
+    pub fn dance(&mut self) -> Statistics {
+        self.dance_with(&[])
+    }
+
+    /// Solves the instance with the given options, numbered from 1 in input
+    /// order, forced into every solution. They are covered before the search
+    /// starts and restored afterwards, so the instance can be solved again
+    /// under other assumptions. Clashing assumptions give no solutions.
+    pub fn dance_with(&mut self, assumptions: &[usize]) -> Statistics {
+        self.dance_recording(assumptions, None)
+    }
+
+    /// Solves the instance as `dance_with` does, recording the search tree
+    /// into `tree` as it goes.
+    pub fn dance_with_tree(&mut self, assumptions: &[usize], tree: &mut SearchTree) -> Statistics {
+        self.dance_recording(assumptions, Some(tree))
+    }
+
+    fn dance_recording(
+        &mut self,
+        assumptions: &[usize],
+        tree: Option<&mut SearchTree>,
+    ) -> Statistics {
+        self.mems = MemCounter::default();
+
+        // Both totals must match, or the links were not restored.
+        if self.config.get_verbosity() >= 3 {
+            self.print_item_totals("before");
+        }
+
+        let option_nodes = if assumptions.is_empty() && self.option_active.iter().all(|&a| a) {
+            Vec::new()
+        } else {
+            self.get_option_nodes()
+        };
+
+        self.apply_deactivations(&option_nodes);
+
+        let mut covered: Vec<bool> = self.item_active.iter().map(|&a| !a).collect();
+        let mut forced = Vec::with_capacity(assumptions.len());
+        let mut clash = false;
+
+        for &k in assumptions {
+            if k == 0 || k > option_nodes.len() {
+                panic!("Assumptions must be options of the instance.");
+            }
+
+            if !self.option_active[k - 1] {
+                clash = true;
+                break;
+            }
+
+            let x = option_nodes[k - 1];
+            let mut p = x;
+
+            while self.get_top(p) > 0 {
+                clash |= covered[self.get_top(p) as usize];
+                p += 1;
+            }
+
+            if clash {
+                break;
+            }
+
+            for q in x..p {
+                let j = self.get_top(q) as usize;
+
+                covered[j] = true;
+                self.cover(j);
+            }
+
+            forced.push(x);
+        }
+
+        let stats = if clash {
+            Statistics {
+                mems: self.mems.get(),
+                ..Statistics::finished(Counts::default(), Duration::ZERO)
+            }
+        } else {
+            self.search(&forced, tree)
+        };
+
+        for &x in forced.iter().rev() {
+            let mut p = x;
+
+            while self.get_top(p) > 0 {
+                p += 1;
+            }
+
+            for q in (x..p).rev() {
+                self.uncover(self.get_top(q) as usize);
+            }
+        }
+
+        self.undo_deactivations(&option_nodes);
+
+        if self.config.get_verbosity() >= 3 {
+            self.print_item_totals("after");
+        }
+
+        if self.config.check_links()
+            && let Err(err) = self.check_links()
+        {
+            panic!("Link check failed after the search: {}", err);
+        }
+
+        stats
+    }
+
+    // Unlinks the nodes of inactive options, then covers inactive items.
+    fn apply_deactivations(&mut self, option_nodes: &[usize]) {
+        for (k, &x) in option_nodes.iter().enumerate() {
+            if self.option_active[k] {
+                continue;
+            }
+
+            let mut q = x;
+
+            while self.get_top(q) > 0 {
+                let u = self.get_up(q);
+                let d = self.get_down(q);
+
+                self.set_down(u, d);
+                self.set_up(d, u);
+                self.remove_node(self.get_top(q) as usize);
+                q += 1;
+            }
+        }
+
+        for i in 1..=self.get_item_count() {
+            if !self.item_active[i] {
+                self.cover(i);
+            }
+        }
+    }
+
+    // Undoes `apply_deactivations` in the reverse order.
+    fn undo_deactivations(&mut self, option_nodes: &[usize]) {
+        for i in (1..=self.get_item_count()).rev() {
+            if !self.item_active[i] {
+                self.uncover(i);
+            }
+        }
+
+        for (k, &x) in option_nodes.iter().enumerate().rev() {
+            if self.option_active[k] {
+                continue;
+            }
+
+            let mut q = x;
+
+            while self.get_top(q) > 0 {
+                q += 1;
+            }
+
+            for q in (x..q).rev() {
+                let u = self.get_up(q);
+                let d = self.get_down(q);
+
+                self.set_down(u, q);
+                self.set_up(d, q);
+                self.add_node(self.get_top(q) as usize);
+            }
+        }
     }
 
-    pub fn dance(&mut self) -> (usize, Duration, usize, usize, usize, usize) {
-        let now = Instant::now();
+    // Runs Algorithm X on the active items. The options in `forced`, given by
+    // their first node, are already covered and are printed with every
+    // solution.
+    fn search(&mut self, forced: &[usize], mut tree: Option<&mut SearchTree>) -> Statistics {
+        let mut monitor = Monitor::new(&self.config);
 
         let z = self.get_list_len() - 1;
         let mut backtrack = vec![0; (-self.get_top(z)).try_into().unwrap()];
@@ -112,104 +503,115 @@ impl DancingLinks {
             None => StdRng::from_os_rng(),
         };
 
-        let mut solution_count = 0;
-        let mut visited_nodes = 0;
-        let mut update_count = 0;
-        let mut max_degree = 0;
-        let mut max_level = 0;
-
-        let timeout = self.config.get_timeout().map(Duration::from_secs);
+        let mut counts = Counts::default();
 
-        let report_delta = Duration::from_secs(self.config.get_report_delta());
-        let mut time_threshold = report_delta;
+        let mut profile = self
+            .config
+            .is_profiling()
+            .then(|| Profile::new(&self.item_names));
 
-        let level_limit = 3 * self.config.get_level_limit();
+        let mut branch_table = self.config.get_branch_depth().map(BranchTable::new);
 
         let show_first = self.config.show_first();
         let solution_interval = self.config.get_solution_interval();
+        let solution_limit = self.config.get_solution_limit();
+        let mut printed_solutions = 0;
 
-        loop {
-            let time_elapsed = now.elapsed();
-
-            if let Some(t) = timeout {
-                if time_elapsed >= t {
-                    println!("TIMEOUT!");
-
-                    return (
-                        solution_count,
-                        time_elapsed,
-                        visited_nodes,
-                        update_count,
-                        max_degree,
-                        max_level,
-                    );
-                }
-            }
-
-            if time_elapsed >= time_threshold {
-                let mut branches = String::new();
-
-                let mut explored = 0.0;
-                let mut d = 1.0;
+        let randomize = self.config.get_randomization_seed().is_some();
 
-                for &x in backtrack.iter().take(level) {
-                    let (position, length) = self.get_option_position(x);
-
-                    let length_char =
-                        char::from_digit(length.try_into().unwrap(), 36).unwrap_or('*');
+        let verbosity = self.config.get_verbosity();
+        let trace_depth = if verbosity >= 2 {
+            self.config.get_choice_depth()
+        } else {
+            0
+        };
 
-                    d *= length as f64;
+        let check_links = self.config.check_links();
 
-                    if let Some(k) = position {
-                        let position_char =
-                            char::from_digit(k.try_into().unwrap(), 36).unwrap_or('*');
+        // The deepest partial solution so far that is not a solution, shown if the
+        // search is stopped.
+        let mut deepest = self.with_positions(forced);
+        let mut deepest_uncovered = self.get_uncovered_items();
 
-                        branches.push_str(&format!("{}{} ", position_char, length_char));
+        loop {
+            if check_links {
+                let mut covered: Vec<bool> = self.item_active.iter().map(|&a| !a).collect();
 
-                        explored += ((k - 1) as f64) / d;
-                    } else {
-                        branches.push_str(&format!("?{} ", length_char));
+                for &x in forced.iter().chain(&backtrack[..level]) {
+                    for q in self.get_option_range(x) {
+                        covered[self.get_top(q) as usize] = true;
                     }
                 }
 
-                let elapsed = time_elapsed.as_secs();
-
-                if branches.len() > level_limit {
-                    branches = branches.chars().take(level_limit).collect::<String>();
-                    branches.push_str("...");
-                } else if !branches.is_empty() {
-                    branches.pop();
+                if let Err(err) = self.check_links_with(&covered) {
+                    panic!("Link check failed after {} nodes: {}", counts.nodes, err);
                 }
+            }
 
-                let s = if solution_count == 1 { "" } else { "s" };
+            let check_exit = exit_level;
+            exit_level = false;
 
-                if level_limit == 0 {
-                    eprintln!(
-                        "{}s: {} solution{}, {:.5} explored",
-                        elapsed, solution_count, s, explored,
-                    );
-                } else {
-                    eprintln!(
-                        "{}s: {} solution{}, {}, {:.5} explored",
-                        elapsed, solution_count, s, branches, explored,
-                    );
+            // A step that does not branch backs up a level and tries the next
+            // option there, if any.
+            let branching = self.get_right(0) != 0 && !check_exit;
+
+            let stop = monitor.check(
+                &counts,
+                self.mems.get(),
+                !check_exit,
+                || {
+                    branching
+                        || level > 0 && {
+                            let x = backtrack[level - 1];
+
+                            self.get_down(x) != self.get_top(x) as usize
+                        }
+                },
+                || {
+                    backtrack
+                        .iter()
+                        .take(level)
+                        .map(|&x| self.get_option_position(x))
+                        .collect()
+                },
+            );
+
+            if let Some(stop) = stop {
+                let solution: Vec<usize> =
+                    forced.iter().chain(&backtrack[..level]).copied().collect();
+
+                self.print_partial_at(
+                    &self.with_positions(&solution),
+                    stop.explored,
+                    &deepest,
+                    &deepest_uncovered,
+                );
+
+                if let Some(table) = &mut branch_table {
+                    table.finish(0, counts.solutions, counts.nodes, monitor.elapsed());
                 }
 
-                time_threshold += report_delta;
-            }
+                self.unwind(&backtrack[..level]);
 
-            let check_exit = exit_level;
-            exit_level = false;
+                return Statistics {
+                    profile,
+                    mems: self.mems.get(),
+                    branches: branch_table,
+                    ..Statistics::stopped(counts, monitor.elapsed(), stop.reason)
+                };
+            }
 
-            if self.get_right(0) != 0 && !check_exit {
-                visited_nodes += 1;
+            if branching {
+                counts.nodes += 1;
 
                 let mut min_length = z;
                 let mut p = self.get_right(0);
                 i = p;
+                self.mems.add(1);
 
                 while p != 0 {
                     let length = self.get_length(p);
+                    self.mems.add(2);
 
                     if length < min_length {
                         min_length = length;
@@ -223,13 +625,14 @@ impl DancingLinks {
                     p = self.get_right(p);
                 }
 
-                if self.config.get_randomization_seed().is_some() {
+                if randomize {
                     p = self.get_right(0);
 
                     candidates = 0;
 
                     while p != 0 {
                         let length = self.get_length(p);
+                        self.mems.add(2);
 
                         if length == min_length {
                             candidates += 1;
@@ -243,31 +646,95 @@ impl DancingLinks {
                     }
                 }
 
-                if max_degree < min_length {
-                    max_degree = min_length;
+                if counts.max_degree < min_length {
+                    counts.max_degree = min_length;
                 }
 
-                update_count += self.cover(i);
+                if let Some(profile) = &mut profile {
+                    profile.add_branch(level, i, min_length);
+                }
+
+                if let Some(tree) = tree.as_deref_mut() {
+                    tree.add_node(
+                        level,
+                        || NodeKind::Branch {
+                            item: self.item_names[i].clone(),
+                            degree: min_length,
+                        },
+                        || self.get_tree_option(backtrack[level - 1]),
+                    );
+                }
+
+                if level < trace_depth {
+                    if verbosity >= 4 {
+                        let solution: Vec<usize> =
+                            forced.iter().chain(&backtrack[..level]).copied().collect();
+
+                        self.print_state(level, &solution);
+                    }
+
+                    if verbosity >= 3 && min_length == 0 {
+                        output::print_dead_end(level, &self.item_names[i]);
+                    }
+                }
+
+                counts.updates += self.cover(i);
 
                 backtrack[level] = self.get_down(i);
             } else {
                 if !check_exit {
-                    visited_nodes += 1;
+                    counts.nodes += 1;
 
-                    if max_level < level + 1 {
-                        max_level = level + 1;
+                    if counts.max_level < level + 1 {
+                        counts.max_level = level + 1;
                     }
 
-                    solution_count += 1;
+                    counts.solutions += 1;
 
-                    if (show_first && solution_count == 1)
-                        || (solution_interval > 0 && solution_count % solution_interval == 0)
+                    if let Some(profile) = &mut profile {
+                        profile.add_solution(level);
+                    }
+
+                    if let Some(tree) = tree.as_deref_mut() {
+                        tree.add_node(
+                            level,
+                            || NodeKind::Solution,
+                            || self.get_tree_option(backtrack[level - 1]),
+                        );
+                    }
+
+                    if verbosity >= 3 && level < trace_depth {
+                        output::print_traced_solution(level, counts.solutions);
+                    }
+
+                    if (show_first && counts.solutions == 1)
+                        || (solution_interval > 0 && counts.solutions % solution_interval == 0)
                     {
-                        println!("Solution {}:", solution_count);
+                        let solution: Vec<usize> =
+                            forced.iter().chain(&backtrack[..level]).copied().collect();
+
+                        self.print_solution(counts.solutions, &solution, printed_solutions == 0);
 
-                        for &x in backtrack.iter().take(level) {
-                            println!(" {}", self.get_option_str(x));
+                        printed_solutions += 1;
+                    }
+
+                    if solution_limit == Some(counts.solutions) {
+                        if let Some(table) = &mut branch_table {
+                            table.finish(0, counts.solutions, counts.nodes, monitor.elapsed());
                         }
+
+                        self.unwind(&backtrack[..level]);
+
+                        return Statistics {
+                            profile,
+                            mems: self.mems.get(),
+                            branches: branch_table,
+                            ..Statistics::stopped(
+                                counts,
+                                monitor.elapsed(),
+                                StopReason::LimitReached,
+                            )
+                        };
                     }
                 }
 
@@ -278,65 +745,196 @@ impl DancingLinks {
                 level -= 1;
 
                 let x = backtrack[level];
-                let mut p = x - 1;
 
-                while p != x {
-                    let j = self.get_top(p);
-                    if j <= 0 {
-                        p = self.get_down(p);
-                    } else {
-                        self.uncover(j.try_into().unwrap());
-                        p -= 1;
-                    }
-                }
+                self.uncover_option(x);
 
-                i = self.get_top(x).try_into().unwrap();
+                i = self.get_top(x) as usize;
                 backtrack[level] = self.get_down(x);
             }
 
             if backtrack[level] == i {
                 self.uncover(i);
                 exit_level = true;
+
+                if let Some(table) = &mut branch_table {
+                    table.finish(level, counts.solutions, counts.nodes, monitor.elapsed());
+                }
             } else {
                 let x = backtrack[level];
                 let mut p = x + 1;
 
+                if let Some(table) = &mut branch_table
+                    && level < table.get_depth()
+                {
+                    table.start(
+                        level,
+                        self.get_tree_option(x),
+                        counts.solutions,
+                        counts.nodes,
+                        monitor.elapsed(),
+                    );
+                }
+
+                if level < trace_depth {
+                    let (position, length) = self.get_option_position(x);
+
+                    output::print_choice(level, &self.get_chosen_option(x, position, length));
+                }
+
                 while p != x {
                     let j = self.get_top(p);
                     if j <= 0 {
                         p = self.get_up(p);
                     } else {
-                        update_count += self.cover(j.try_into().unwrap());
+                        counts.updates += self.cover(j as usize);
                         p += 1;
                     }
                 }
 
                 level += 1;
 
-                if max_level < level {
-                    max_level = level;
+                if counts.max_level < level {
+                    counts.max_level = level;
+                }
+
+                if self.get_right(0) != 0 && deepest.len() < forced.len() + level {
+                    let solution: Vec<usize> =
+                        forced.iter().chain(&backtrack[..level]).copied().collect();
+
+                    deepest = self.with_positions(&solution);
+                    deepest_uncovered = self.get_uncovered_items();
                 }
             }
         }
 
-        (
-            solution_count,
-            now.elapsed(),
-            visited_nodes,
-            update_count,
-            max_degree,
-            max_level,
-        )
+        if let Some(table) = &mut branch_table {
+            table.finish(0, counts.solutions, counts.nodes, monitor.elapsed());
+        }
+
+        Statistics {
+            profile,
+            mems: self.mems.get(),
+            branches: branch_table,
+            ..Statistics::finished(counts, monitor.elapsed())
+        }
+    }
+
+    fn print_solution(&self, index: usize, solution: &[usize], first: bool) {
+        self.print_solution_at(index, &self.with_positions(solution), first);
+    }
+
+    // Pairs the nodes of chosen options with their positions in the lists of
+    // the items the search branched on.
+    fn with_positions(&self, solution: &[usize]) -> Vec<(usize, Option<usize>, usize)> {
+        solution
+            .iter()
+            .map(|&x| {
+                let (position, length) = self.get_option_position(x);
+
+                (x, position, length)
+            })
+            .collect()
+    }
+
+    /// Prints a solution given, for each level, the node of the chosen option
+    /// in the branching item together with its position in that item's list.
+    fn print_solution_at(
+        &self,
+        index: usize,
+        solution: &[(usize, Option<usize>, usize)],
+        first: bool,
+    ) {
+        let options = self.get_chosen_options(solution);
+
+        output::print_solution(self.config.get_format(), index, &options, first);
+    }
+
+    /// Prints the partial solution of a stopped search and the deepest one it
+    /// reached, given as for `print_solution_at`, with the primary items the
+    /// latter leaves uncovered.
+    fn print_partial_at(
+        &self,
+        solution: &[(usize, Option<usize>, usize)],
+        explored: f64,
+        deepest: &[(usize, Option<usize>, usize)],
+        uncovered: &[usize],
+    ) {
+        let partial = output::Partial {
+            options: self.get_chosen_options(solution),
+            explored,
+            deepest: self.get_chosen_options(deepest),
+            uncovered: uncovered
+                .iter()
+                .map(|&i| self.item_names[i].as_str())
+                .collect(),
+        };
+
+        output::print_partial(self.config.get_format(), &partial);
+    }
+
+    // Gives the number and text of the option of node `x`, as an edge of the
+    // search tree or a branch counted by `--branch-counts`.
+    fn get_tree_option(&self, x: usize) -> (usize, String) {
+        let (position, length) = self.get_option_position(x);
+        let option = self.get_chosen_option(x, position, length);
+
+        (option.option, option.to_text())
+    }
+
+    // Shows the options chosen down to `level` and the items left with their
+    // lengths, as `print_state` does in xcc.c.
+    fn print_state(&self, level: usize, solution: &[usize]) {
+        let options = self.get_chosen_options(&self.with_positions(solution));
+        let items: Vec<(&str, usize)> = self
+            .get_uncovered_items()
+            .into_iter()
+            .map(|i| (self.item_names[i].as_str(), self.get_length(i)))
+            .collect();
+
+        output::print_state(level, &options, &items);
+    }
+
+    fn print_item_totals(&self, when: &str) {
+        let totals: Vec<(&str, usize)> = (1..=self.get_item_count())
+            .map(|i| (self.item_names[i].as_str(), self.get_length(i)))
+            .collect();
+
+        output::print_item_totals(when, &totals);
+    }
+
+    // Lists the primary items that are not covered yet.
+    fn get_uncovered_items(&self) -> Vec<usize> {
+        let mut items = Vec::new();
+        let mut p = self.get_right(0);
+
+        while p != 0 {
+            items.push(p);
+            p = self.get_right(p);
+        }
+
+        items
+    }
+
+    fn get_chosen_options(
+        &self,
+        solution: &[(usize, Option<usize>, usize)],
+    ) -> Vec<ChosenOption<'_>> {
+        solution
+            .iter()
+            .map(|&(x, position, length)| self.get_chosen_option(x, position, length))
+            .collect()
     }
 
     fn cover(&mut self, i: usize) -> usize {
         let mut updates = 1;
 
         let mut p = self.get_down(i);
+        self.mems.add(1);
 
         while p != i {
             updates += self.hide(p);
             p = self.get_down(p);
+            self.mems.add(1);
         }
 
         let l = self.get_left(i);
@@ -344,6 +942,7 @@ impl DancingLinks {
 
         self.set_right(l, r);
         self.set_left(r, l);
+        self.mems.add(3);
 
         updates
     }
@@ -357,6 +956,7 @@ impl DancingLinks {
             let t = self.get_top(q);
             let u = self.get_up(q);
             let d = self.get_down(q);
+            self.mems.add(1);
 
             if t <= 0 {
                 q = u;
@@ -365,7 +965,8 @@ impl DancingLinks {
 
                 self.set_down(u, d);
                 self.set_up(d, u);
-                self.remove_node(t.try_into().unwrap());
+                self.remove_node(t as usize);
+                self.mems.add(4);
                 q += 1;
             }
         }
@@ -379,12 +980,40 @@ impl DancingLinks {
 
         self.set_right(l, i);
         self.set_left(r, i);
+        self.mems.add(3);
 
         let mut p = self.get_up(i);
+        self.mems.add(1);
 
         while p != i {
             self.unhide(p);
             p = self.get_up(p);
+            self.mems.add(1);
+        }
+    }
+
+    // Undoes the choices of the search so far, given by `backtrack`, so the
+    // structure can be reused after stopping early.
+    fn unwind(&mut self, backtrack: &[usize]) {
+        for &x in backtrack.iter().rev() {
+            self.uncover_option(x);
+            self.uncover(self.get_top(x) as usize);
+        }
+    }
+
+    // Uncovers the items of the option of node `x` other than the one it was
+    // chosen for, in the reverse order they were covered.
+    fn uncover_option(&mut self, x: usize) {
+        let mut p = x - 1;
+
+        while p != x {
+            let j = self.get_top(p);
+            if j <= 0 {
+                p = self.get_down(p);
+            } else {
+                self.uncover(j as usize);
+                p -= 1;
+            }
         }
     }
 
@@ -395,13 +1024,15 @@ impl DancingLinks {
             let t = self.get_top(q);
             let u = self.get_up(q);
             let d = self.get_down(q);
+            self.mems.add(1);
 
             if t <= 0 {
                 q = d;
             } else {
                 self.set_down(u, q);
                 self.set_up(d, q);
-                self.add_node(t.try_into().unwrap());
+                self.add_node(t as usize);
+                self.mems.add(4);
                 q -= 1;
             }
         }
@@ -423,6 +1054,260 @@ impl DancingLinks {
         (-self.get_top(self.get_list_len() - 1)).try_into().unwrap()
     }
 
+    pub fn get_option_data(&self, option: usize) -> &OptionData {
+        &self.option_data[option - 1]
+    }
+
+    pub fn find_item(&self, name: &str) -> Option<usize> {
+        self.item_index.get(name).copied()
+    }
+
+    pub fn get_item_name(&self, i: usize) -> &str {
+        &self.item_names[i]
+    }
+
+    /// Returns the option made of exactly `items`, in any order.
+    pub fn find_option(&self, items: &[usize]) -> Option<usize> {
+        let mut key = items.to_vec();
+        key.sort_unstable();
+
+        let &i = key.first()?;
+
+        let mut p = self.get_down(i);
+
+        while p != i {
+            let mut q = p;
+
+            while self.get_top(q) > 0 {
+                q += 1;
+            }
+
+            let start = self.get_up(q);
+
+            if q - start == key.len() {
+                let mut option: Vec<usize> = (start..q).map(|r| self.get_top(r) as usize).collect();
+                option.sort_unstable();
+
+                if option == key {
+                    return Some((-self.get_top(q)) as usize);
+                }
+            }
+
+            p = self.get_down(p);
+        }
+
+        None
+    }
+
+    /// Checks that the links are consistent, as `sanity` does in xcc.c: lists
+    /// are doubly linked, item lengths match the sizes of their lists and the
+    /// active items are exactly the uncovered ones. Between searches nothing
+    /// is covered. Returns the first violation found.
+    pub fn check_links(&self) -> Result<(), String> {
+        self.check_links_with(&vec![false; self.get_item_count() + 1])
+    }
+
+    fn check_links_with(&self, covered: &[bool]) -> Result<(), String> {
+        let n1 = self.primary;
+        let n = self.get_item_count();
+        let z = self.get_list_len();
+
+        let name = |i: usize| format!("{} ({})", self.item_names[i], i);
+
+        for (root, items, kind) in [(0, 1..=n1, "primary"), (n + 1, n1 + 1..=n, "secondary")] {
+            let mut listed = vec![false; n + 1];
+            let mut p = root;
+
+            loop {
+                let r = self.get_right(p);
+
+                if r > n + 1 {
+                    return Err(format!(
+                        "Item {} links right to {}, out of the items.",
+                        p, r
+                    ));
+                }
+
+                if self.get_left(r) != p {
+                    return Err(format!(
+                        "Item {} links right to {} but item {} links left to {}.",
+                        p,
+                        r,
+                        r,
+                        self.get_left(r),
+                    ));
+                }
+
+                if r == root {
+                    break;
+                }
+
+                if !items.contains(&r) {
+                    return Err(format!(
+                        "The list of {} items reaches item {}, which is not one of them.",
+                        kind,
+                        name(r),
+                    ));
+                }
+
+                if listed[r] {
+                    return Err(format!(
+                        "Item {} is twice in the list of active items.",
+                        name(r),
+                    ));
+                }
+
+                if covered[r] {
+                    return Err(format!(
+                        "Item {} is covered but still in the list of active items.",
+                        name(r),
+                    ));
+                }
+
+                listed[r] = true;
+                p = r;
+            }
+
+            if let Some(i) = items.clone().find(|&i| !covered[i] && !listed[i]) {
+                return Err(format!(
+                    "Item {} is not covered but missing from the list of active items.",
+                    name(i),
+                ));
+            }
+        }
+
+        for i in 1..=n {
+            let mut count = 0;
+            let mut p = i;
+
+            loop {
+                let d = self.get_down(p);
+
+                if d >= z {
+                    return Err(format!("Node {} links down to {}, out of the nodes.", p, d));
+                }
+
+                if self.get_up(d) != p {
+                    return Err(format!(
+                        "Node {} links down to {} but node {} links up to {}.",
+                        p,
+                        d,
+                        d,
+                        self.get_up(d),
+                    ));
+                }
+
+                if d == i {
+                    break;
+                }
+
+                if self.get_top(d) != i as isize {
+                    return Err(format!(
+                        "Node {} is in the list of item {} but belongs to {}.",
+                        d,
+                        name(i),
+                        self.get_top(d),
+                    ));
+                }
+
+                count += 1;
+
+                if count > z {
+                    return Err(format!(
+                        "The list of item {} never gets back to it.",
+                        name(i)
+                    ));
+                }
+
+                if !covered[i] {
+                    let option = self.get_option_range(d);
+
+                    if let Some(q) = option.clone().find(|&q| covered[self.get_top(q) as usize]) {
+                        return Err(format!(
+                            "Option {} is in the list of uncovered item {} but has covered item {}.",
+                            -self.get_top(option.end),
+                            name(i),
+                            name(self.get_top(q) as usize),
+                        ));
+                    }
+                }
+
+                p = d;
+            }
+
+            if count != self.get_length(i) {
+                return Err(format!(
+                    "Item {} has length {} but {} nodes in its list.",
+                    name(i),
+                    self.get_length(i),
+                    count,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Gives the nodes of the option holding node `x`.
+    fn get_option_range(&self, x: usize) -> Range<usize> {
+        let mut p = x;
+
+        while self.get_top(p) > 0 {
+            p += 1;
+        }
+
+        self.get_up(p)..p
+    }
+
+    /// Looks for input that is valid but likely a mistake, like a primary item
+    /// in no option, which leaves the instance with no solutions.
+    pub fn get_warnings(&self) -> Vec<String> {
+        utils::get_warnings(
+            self.primary,
+            &self.item_names[..=self.get_item_count()],
+            &self.get_option_items(),
+            &self.option_data,
+        )
+    }
+
+    pub fn get_option_items(&self) -> Vec<Vec<usize>> {
+        let mut options = Vec::with_capacity(self.get_option_count());
+        let mut option = Vec::new();
+
+        for p in self.get_item_count() + 2..self.get_list_len() {
+            let t = self.get_top(p);
+
+            if t > 0 {
+                option.push(t.try_into().unwrap());
+            } else {
+                options.push(mem::take(&mut option));
+            }
+        }
+
+        options
+    }
+
+    pub fn print_options(&self, index: usize, options: &[usize], first: bool) {
+        let option_nodes = self.get_option_nodes();
+
+        let solution: Vec<usize> = options.iter().map(|&k| option_nodes[k - 1]).collect();
+
+        self.print_solution(index, &solution, first);
+    }
+
+    /// Returns the first node of every option, in input order.
+    fn get_option_nodes(&self) -> Vec<usize> {
+        let mut option_nodes = Vec::with_capacity(self.get_option_count());
+
+        for p in self.get_item_count() + 1..self.get_list_len() - 1 {
+            if self.get_top(p) <= 0 {
+                option_nodes.push(p + 1);
+            }
+        }
+
+        option_nodes
+    }
+
     pub fn get_list_len(&self) -> usize {
         self.node_list.len()
     }
@@ -455,89 +1340,85 @@ impl DancingLinks {
         }
     }
 
-    fn get_option_str(&self, i: usize) -> String {
-        let t = self.get_top(i);
-
-        if t <= 0 {
+    fn get_chosen_option(
+        &self,
+        i: usize,
+        position: Option<usize>,
+        length: usize,
+    ) -> ChosenOption<'_> {
+        if self.get_top(i) <= 0 {
             panic!("Node {i} does not correspond to an item in an option.");
         }
 
-        let mut option_str = self.item_header[t as usize].name.clone().unwrap();
-
-        let mut p = i + 1;
-
-        while p != i {
-            let t = self.get_top(p);
-
-            if t <= 0 {
-                p = self.get_up(p);
-                continue;
-            }
-
-            option_str.push_str(&format!(
-                " {}",
-                self.item_header[t as usize].name.clone().unwrap()
-            ));
+        let mut p = i;
 
+        while self.get_top(p) > 0 {
             p += 1;
         }
 
-        let (position, length) = self.get_option_position(i);
+        let option = (-self.get_top(p)) as usize;
 
-        if let Some(k) = position {
-            option_str.push_str(&format!(" ({} of {})", k, length));
-        } else {
-            option_str.push_str(&format!(" (? of {})", length));
-        }
+        let start = self.get_up(p);
 
-        option_str
+        let items = (start..p)
+            .map(|q| self.item_names[self.get_top(q) as usize].as_str())
+            .collect();
+
+        ChosenOption {
+            option,
+            items,
+            first: i - start,
+            position,
+            length,
+            data: &self.option_data[option - 1],
+        }
     }
 
     fn get_length(&self, i: usize) -> usize {
-        self.item_header[i].length
+        self.item_header.length[i] as usize
     }
 
     fn get_left(&self, i: usize) -> usize {
-        self.item_header[i].left
+        self.item_header.left[i] as usize
     }
 
     fn get_right(&self, i: usize) -> usize {
-        self.item_header[i].right
+        self.item_header.right[i] as usize
     }
 
     fn get_top(&self, i: usize) -> isize {
-        self.node_list[i].top
+        self.node_list.top[i] as isize
     }
 
     fn get_up(&self, i: usize) -> usize {
-        self.node_list[i].up
+        self.node_list.up[i] as usize
     }
 
     fn get_down(&self, i: usize) -> usize {
-        self.node_list[i].down
+        self.node_list.down[i] as usize
     }
 
     fn set_left(&mut self, i: usize, l: usize) {
-        self.item_header[i].left = l;
+        self.item_header.left[i] = l as u32;
     }
 
     fn set_right(&mut self, i: usize, r: usize) {
-        self.item_header[i].right = r;
+        self.item_header.right[i] = r as u32;
     }
 
     fn set_up(&mut self, i: usize, u: usize) {
-        self.node_list[i].up = u;
+        self.node_list.up[i] = u as u32;
     }
 
     fn set_down(&mut self, i: usize, d: usize) {
-        self.node_list[i].down = d;
+        self.node_list.down[i] = d as u32;
     }
 
     fn add_node(&mut self, i: usize) {
-        self.item_header[i].add_node();
+        self.item_header.length[i] += 1;
     }
 
     fn remove_node(&mut self, i: usize) {
-        self.item_header[i].remove_node();
+        self.item_header.length[i] -= 1;
     }
 }
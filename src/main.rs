@@ -1,10 +1,37 @@
+use std::any::Any;
+use std::cell::Cell;
+use std::collections::HashMap;
 use std::env;
-use std::io;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter};
+use std::panic::{self, AssertUnwindSafe};
 use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, mpsc};
+use std::thread;
 use std::time::Instant;
 
-use dlx::DancingLinks;
+use dlx::bitset::{self, BitsetDancer};
+use dlx::cells::DancingCells;
 use dlx::config::*;
+use dlx::input::BatchInstance;
+use dlx::tree::SearchTree;
+use dlx::verify::{self, Verifier};
+use dlx::{DancingLinks, Statistics, Verdict, export, input, output, snapshot};
+
+const INPUT_BUFFER_SIZE: usize = 1 << 16;
+
+// As the shell reports a process killed by SIGINT.
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+// Batch instances each thread may have solved or be solving ahead of the
+// ones printed.
+const PENDING_PER_JOB: usize = 4;
+
+thread_local! {
+    // Whether this thread is reading or solving an instance of a batch.
+    static IN_INSTANCE: Cell<bool> = const { Cell::new(false) };
+}
 
 fn main() {
     let now = Instant::now();
@@ -21,66 +48,46 @@ fn main() {
         process::exit(0);
     }
 
-    let mut item_buffer = String::new();
-
-    let (primary_items, secondary_items) = loop {
-        io::stdin()
-            .read_line(&mut item_buffer)
-            .expect("Failed to read line.");
-
-        if item_buffer.trim().is_empty() {
-            continue;
+    if let Some(delimiter) = config.get_batch_delimiter() {
+        if config.get_load_file().is_some()
+            || config.get_save_file().is_some()
+            || config.get_export_format().is_some()
+            || config.get_model_file().is_some()
+            || config.get_assume_file().is_some()
+            || config.get_tree_file().is_some()
+            || config.get_verify_file().is_some()
+            || config.is_unique()
+        {
+            panic!("--batch (-b) cannot be combined with -L, -S, -x, -d, -a, -T, -C or -u.");
         }
 
-        if !item_buffer.is_ascii() {
-            panic!("Item names should belong to ASCII range.");
-        }
+        run_batch(&config, delimiter);
 
-        if item_buffer.matches('|').count() > 1 {
-            panic!("Item type separator \'|\' can only appear once.");
-        }
+        return;
+    }
 
-        let mut items = item_buffer.split('|');
+    if let Some(verify_file) = config.get_verify_file() {
+        verify_solutions(&config, verify_file);
 
-        let primary: Vec<&str> = items.next().unwrap().split_whitespace().collect();
+        return;
+    }
 
-        let secondary: Vec<&str> = match items.next() {
-            Some(s) => s.split_whitespace().collect(),
-            None => Vec::new(),
-        };
+    let mut dlx = if let Some(load_file) = config.get_load_file() {
+        let file = File::open(load_file).expect("Failed to open snapshot file.");
 
-        if primary.is_empty() {
-            panic!("Primary items are required.");
-        }
+        snapshot::load(&config, BufReader::new(file)).unwrap_or_else(|err| {
+            panic!("{}", err);
+        })
+    } else {
+        let stdin = BufReader::with_capacity(INPUT_BUFFER_SIZE, io::stdin().lock());
 
-        break (primary, secondary);
+        read_instance(&config, stdin)
     };
 
-    let mut dlx = DancingLinks::new(
-        &config,
-        primary_items.as_slice(),
-        secondary_items.as_slice(),
-    );
-
-    let mut option_buffer = String::new();
-
-    loop {
-        let read_bytes = io::stdin()
-            .read_line(&mut option_buffer)
-            .expect("Failed to read line.");
+    if let Some(save_file) = config.get_save_file() {
+        let file = File::create(save_file).expect("Failed to create snapshot file.");
 
-        if read_bytes == 0 {
-            break;
-        }
-
-        if option_buffer.trim().is_empty() {
-            option_buffer.clear();
-            continue;
-        }
-
-        dlx.add_option(&option_buffer);
-
-        option_buffer.clear();
+        snapshot::save(&dlx, BufWriter::new(file)).expect("Failed to write snapshot.");
     }
 
     let preprocess_time = now.elapsed();
@@ -94,31 +101,332 @@ fn main() {
         dlx.get_option_count(),
     );
 
-    let (solution_count, elapsed_time, visited_nodes, update_count, max_degree, max_level) = dlx.dance();
+    if config.is_verbose() {
+        for warning in dlx.get_warnings() {
+            output::print_warning(&warning);
+        }
+    }
 
-    let s = if solution_count == 1 { "" } else { "s" };
+    let format = config.get_format();
 
-    if config.is_verbose() {
-        let total_time = preprocess_time + elapsed_time;
+    if let Some(export_format) = config.get_export_format() {
+        let writer = io::stdout().lock();
+
+        match export_format {
+            ExportFormat::Dimacs => export::write_dimacs(&dlx, config.get_cnf_encoding(), writer),
+            ExportFormat::Opb => export::write_opb(&dlx, writer),
+            ExportFormat::Lp => export::write_lp(&dlx, writer),
+        }
+        .expect("Failed to write exported instance.");
+
+        return;
+    }
+
+    if let Some(model_file) = config.get_model_file() {
+        let file = File::open(model_file).expect("Failed to open model file.");
+
+        let options = export::decode_model(&dlx, BufReader::new(file)).unwrap_or_else(|err| {
+            panic!("{}", err);
+        });
+
+        output::print_header(format);
+        dlx.print_options(1, &options, true);
+        output::print_footer(format);
+
+        return;
+    }
+
+    let assumptions = config.get_assume_file().map(|assume_file| {
+        let file = File::open(assume_file).expect("Failed to open assumptions file.");
+
+        input::read_assumptions(&dlx, BufReader::new(file)).unwrap_or_else(|err| {
+            panic!("{}", err);
+        })
+    });
+
+    let mut tree = config
+        .get_tree_file()
+        .map(|_| SearchTree::new(config.get_tree_depth(), Some(config.get_tree_nodes())));
+
+    output::print_header(format);
+
+    // Until then Ctrl-C kills the program as usual.
+    dlx::install_signal_handlers().expect("Failed to install signal handlers.");
+
+    let stats = solve(&config, &mut dlx, assumptions, tree.as_mut());
+
+    output::print_statistics(&config, &stats, preprocess_time);
+
+    if let (Some(tree_file), Some(tree)) = (config.get_tree_file(), tree) {
+        let file = File::create(tree_file).expect("Failed to create search tree file.");
+
+        tree.write(config.get_tree_format(), BufWriter::new(file))
+            .expect("Failed to write search tree.");
+
+        eprintln!("Wrote {} nodes of the search tree.", tree.get_node_count());
+    }
+
+    if stats.interrupted {
+        process::exit(INTERRUPTED_EXIT_CODE);
+    }
+
+    if config.is_unique() {
+        process::exit(match stats.verdict() {
+            Verdict::Unique => 0,
+            Verdict::None => 1,
+            Verdict::Multiple => 2,
+            Verdict::Unknown => 3,
+        });
+    }
+}
+
+// Checks the solutions in `verify_file` against the instance on stdin, read
+// as it is given rather than into the links of a search, and exits with 1 if
+// any of them is wrong.
+fn verify_solutions(config: &Config, verify_file: &str) {
+    if config.get_load_file().is_some() {
+        panic!("--verify (-C) checks the instance on stdin and cannot be combined with -L.");
+    }
+
+    let stdin = BufReader::with_capacity(INPUT_BUFFER_SIZE, io::stdin().lock());
+    let instance = input::read_instance(config, stdin);
+
+    let file = File::open(verify_file).expect("Failed to open solution file.");
+
+    let solutions = verify::read_solutions(BufReader::new(file)).unwrap_or_else(|err| {
+        panic!("{}", err);
+    });
+
+    let format = config.get_format();
+    let verifier = Verifier::new(&instance);
+    let mut invalid = 0;
+
+    output::print_header(format);
+
+    for (k, solution) in solutions.iter().enumerate() {
+        let violations = verifier.check(&solution.options);
 
-        println!(
-            "The tree's maximum degree is {}, its depth is {}.",
-            max_degree, max_level,
-        );
+        if !violations.is_empty() {
+            invalid += 1;
+        }
+
+        output::print_verification(format, solution.index, &violations, k == 0);
+    }
+
+    output::print_verification_summary(format, solutions.len() - invalid, invalid);
+
+    process::exit(if invalid == 0 { 0 } else { 1 });
+}
+
+fn read_instance<R: BufRead>(config: &Config, reader: R) -> DancingLinks {
+    match config.get_input_format() {
+        InputFormat::Text => input::read_text(config, reader),
+        InputFormat::Json => input::read_json(config, reader),
+        InputFormat::Matrix => input::read_matrix(config, reader),
+    }
+}
 
-        println!(
-            "{:.5?} overall: {:.5?} processing input + {:.5?} dancing.",
-            total_time, preprocess_time, elapsed_time,
-        );
+fn solve(
+    config: &Config,
+    dlx: &mut DancingLinks,
+    assumptions: Option<Vec<usize>>,
+    tree: Option<&mut SearchTree>,
+) -> Statistics {
+    let assumptions = assumptions.as_deref().unwrap_or_default();
+
+    let engine = match config.get_engine() {
+        // Only the dlx engine traces its choices, checks its links and counts
+        // mems.
+        Engine::Auto
+            if bitset::is_suitable(dlx)
+                && config.get_verbosity() < 2
+                && !config.check_links()
+                && !cfg!(feature = "mems") =>
+        {
+            Engine::Bitset
+        }
+        Engine::Auto => Engine::Dlx,
+        engine => engine,
+    };
 
-        println!(
-            "{:.5?} per solution.",
-            elapsed_time / (solution_count.try_into().unwrap())
-        );
+    match (engine, tree) {
+        (Engine::Bitset, tree) => {
+            let Some(bitset) = BitsetDancer::new(dlx) else {
+                panic!(
+                    "The bitset engine supports up to {} primary items.",
+                    bitset::MAX_PRIMARY_ITEMS,
+                );
+            };
+
+            match tree {
+                Some(tree) => bitset.dance_with_tree(assumptions, tree),
+                None => bitset.dance_with(assumptions),
+            }
+        }
+        (Engine::Cells, Some(tree)) => {
+            DancingCells::from_links(dlx).dance_with_tree(assumptions, tree)
+        }
+        (Engine::Cells, None) => DancingCells::from_links(dlx).dance_with(assumptions),
+        (_, Some(tree)) => dlx.dance_with_tree(assumptions, tree),
+        (_, None) => dlx.dance_with(assumptions),
     }
+}
+
+fn run_batch(config: &Config, delimiter: &str) {
+    let now = Instant::now();
+
+    let stdin = BufReader::with_capacity(INPUT_BUFFER_SIZE, io::stdin());
+    let instances = input::read_batch(stdin, delimiter);
+
+    let format = config.get_format();
+
+    dlx::install_signal_handlers().expect("Failed to install signal handlers.");
+
+    // The panic of an instance is reported with it rather than on stderr.
+    let default_hook = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        if !IN_INSTANCE.get() {
+            default_hook(info);
+        }
+    }));
+
+    // After a SIGINT every instance left stops at once, reporting it.
+    let interrupted = AtomicBool::new(false);
+
+    // Everything an instance prints is captured, so that it can be tagged and
+    // kept apart from the other instances.
+    let solve_instance = |instance: &BatchInstance| {
+        let now = Instant::now();
+
+        IN_INSTANCE.set(true);
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            output::capture(|| {
+                let mut dlx = read_instance(config, instance.text.as_slice());
+
+                let preprocess_time = now.elapsed();
+
+                output::print_header(format);
+
+                let stats = solve(config, &mut dlx, None, None);
+
+                output::print_statistics(config, &stats, preprocess_time);
+
+                if stats.interrupted {
+                    interrupted.store(true, Ordering::Relaxed);
+                }
+            })
+        }));
 
-    println!(
-        "Found {} solution{} in {:.5?} visiting {} nodes doing {} updates.",
-        solution_count, s, elapsed_time, visited_nodes, update_count,
+        IN_INSTANCE.set(false);
+
+        result
+            .map(|((), captured)| captured)
+            .map_err(get_panic_message)
+    };
+
+    let mut instance_count = 0;
+    let mut failed = 0;
+
+    let mut print_instance =
+        |k: usize, name: Option<&str>, result: Result<String, String>| match result {
+            Ok(captured) => output::print_instance(format, k + 1, name, &captured, k == 0),
+            Err(message) => {
+                output::print_instance_error(format, k + 1, name, &message, k == 0);
+                failed += 1;
+            }
+        };
+
+    output::print_batch_header(format);
+
+    if config.get_jobs() == 1 {
+        for instance in instances {
+            print_instance(
+                instance_count,
+                instance.name.as_deref(),
+                solve_instance(&instance),
+            );
+            instance_count += 1;
+        }
+    } else {
+        // Instances are read as threads become free, and a thread only takes
+        // one when fewer than PENDING_PER_JOB per thread are taken but not
+        // printed yet, so that a slow instance does not make the others pile
+        // up in memory behind it.
+        let window = PENDING_PER_JOB * config.get_jobs();
+        let (permit_sender, permits) = mpsc::sync_channel(window);
+
+        for _ in 0..window {
+            permit_sender.send(()).unwrap();
+        }
+
+        let permits = Mutex::new(permits);
+        let instances = Mutex::new(instances.enumerate());
+        let (sender, receiver) = mpsc::channel();
+
+        thread::scope(|scope| {
+            for _ in 0..config.get_jobs() {
+                let sender = sender.clone();
+                let permits = &permits;
+                let instances = &instances;
+                let solve_instance = &solve_instance;
+
+                scope.spawn(move || {
+                    while permits.lock().unwrap().recv().is_ok()
+                        && let Some((k, instance)) = instances.lock().unwrap().next()
+                    {
+                        let result = solve_instance(&instance);
+
+                        sender.send((k, instance.name, result)).unwrap();
+                    }
+                });
+            }
+
+            drop(sender);
+
+            // Results arrive in any order but are printed in input order.
+            let mut pending = HashMap::new();
+
+            for (k, name, result) in receiver {
+                pending.insert(k, (name, result));
+
+                while let Some((name, result)) = pending.remove(&instance_count) {
+                    print_instance(instance_count, name.as_deref(), result);
+                    instance_count += 1;
+
+                    permit_sender.send(()).unwrap();
+                }
+            }
+        });
+    }
+
+    output::print_batch_footer(format);
+
+    eprintln!(
+        "Solved {} instances in {:.5?}.",
+        instance_count,
+        now.elapsed(),
     );
+
+    if interrupted.load(Ordering::Relaxed) {
+        process::exit(INTERRUPTED_EXIT_CODE);
+    }
+
+    if failed > 0 {
+        eprintln!("{} of them failed.", failed);
+
+        process::exit(1);
+    }
+}
+
+// Gives the message a panic was raised with.
+fn get_panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => String::from("Unknown error."),
+        },
+    }
 }
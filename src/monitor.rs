@@ -0,0 +1,195 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
+
+use signal_hook::consts::SIGINT;
+
+use crate::config::{Config, OutputFormat};
+use crate::output::{self, Progress};
+
+// Reading the clock is expensive compared to a single step, so it is only done
+// every few steps.
+const CLOCK_CHECK_INTERVAL: usize = 1 << 10;
+
+// Set by the signal handlers and read by every search.
+static INTERRUPTED: LazyLock<Arc<AtomicBool>> = LazyLock::new(Arc::default);
+static REPORT_REQUESTED: LazyLock<Arc<AtomicBool>> = LazyLock::new(Arc::default);
+
+/// Makes SIGINT stop the running search as if it timed out, printing the
+/// partial solution and the statistics, and SIGUSR1 print a progress report
+/// right away. A second SIGINT kills the process.
+pub fn install_signal_handlers() -> io::Result<()> {
+    signal_hook::flag::register_conditional_shutdown(SIGINT, 130, Arc::clone(&INTERRUPTED))?;
+    signal_hook::flag::register(SIGINT, Arc::clone(&INTERRUPTED))?;
+
+    #[cfg(unix)]
+    signal_hook::flag::register(signal_hook::consts::SIGUSR1, Arc::clone(&REPORT_REQUESTED))?;
+
+    Ok(())
+}
+
+/// Counts kept by every engine while it searches, which end up in its
+/// `Statistics`.
+#[derive(Default)]
+pub(crate) struct Counts {
+    pub(crate) solutions: usize,
+    pub(crate) nodes: usize,
+    pub(crate) updates: usize,
+    pub(crate) max_degree: usize,
+    pub(crate) max_level: usize,
+}
+
+/// Why a search stopped before exploring its whole tree.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StopReason {
+    Timeout,
+    LimitReached,
+    Interrupted,
+}
+
+/// A search that has to stop, with the fraction of its tree it explored.
+pub(crate) struct Stop {
+    pub(crate) reason: StopReason,
+    pub(crate) explored: f64,
+}
+
+enum Event {
+    Stop(StopReason),
+    Report(Progress),
+}
+
+/// Decides when a search has to stop or print a progress report, from the
+/// clock and from its node and update counts.
+pub(crate) struct Monitor {
+    now: Instant,
+    format: OutputFormat,
+    level_limit: usize,
+    steps: usize,
+    timeout: Option<Duration>,
+    report_delta: Duration,
+    time_threshold: Duration,
+    report_nodes: Option<usize>,
+    node_threshold: usize,
+    max_nodes: Option<usize>,
+    max_updates: Option<usize>,
+}
+
+impl Monitor {
+    pub(crate) fn new(config: &Config) -> Self {
+        let report_delta = Duration::from_secs(config.get_report_delta());
+
+        Monitor {
+            now: Instant::now(),
+            format: config.get_format(),
+            level_limit: config.get_level_limit(),
+            steps: 0,
+            timeout: config.get_timeout().map(Duration::from_secs),
+            report_delta,
+            time_threshold: report_delta,
+            report_nodes: config.get_report_nodes(),
+            node_threshold: config.get_report_nodes().unwrap_or(0),
+            max_nodes: config.get_max_nodes(),
+            max_updates: config.get_max_updates(),
+        }
+    }
+
+    /// Called once per step of the search, given whether the step enters a
+    /// new node, whether it may make updates, and the position of the option
+    /// tried at each level and the number of options of its item. Prints the
+    /// progress reports, and tells why the search has to stop, if it does.
+    ///
+    /// The node and update limits only stop a step that would go past them,
+    /// so that a search whose whole tree fits in them is finished.
+    pub(crate) fn check(
+        &mut self,
+        counts: &Counts,
+        mems: Option<usize>,
+        enters_node: bool,
+        updates: impl FnOnce() -> bool,
+        branches: impl FnOnce() -> Vec<(Option<usize>, usize)>,
+    ) -> Option<Stop> {
+        match self.poll(counts.nodes, counts.updates, enters_node, updates)? {
+            Event::Report(progress) => {
+                output::print_report(
+                    progress,
+                    counts.solutions,
+                    mems,
+                    &branches(),
+                    self.level_limit,
+                );
+
+                None
+            }
+            Event::Stop(reason) => {
+                match reason {
+                    StopReason::Timeout => output::print_timeout(self.format),
+                    StopReason::LimitReached => output::print_limit(self.format),
+                    StopReason::Interrupted => output::print_interrupted(self.format),
+                }
+
+                Some(Stop {
+                    reason,
+                    explored: output::explored(&branches()),
+                })
+            }
+        }
+    }
+
+    fn poll(
+        &mut self,
+        visited_nodes: usize,
+        update_count: usize,
+        enters_node: bool,
+        updates: impl FnOnce() -> bool,
+    ) -> Option<Event> {
+        if (enters_node && self.max_nodes.is_some_and(|m| visited_nodes >= m))
+            || (self.max_updates.is_some_and(|m| update_count >= m) && updates())
+        {
+            return Some(Event::Stop(StopReason::LimitReached));
+        }
+
+        if let Some(n) = self.report_nodes
+            && visited_nodes >= self.node_threshold
+        {
+            self.node_threshold += n;
+
+            return Some(Event::Report(Progress::Nodes(visited_nodes)));
+        }
+
+        let check_clock = self.steps.is_multiple_of(CLOCK_CHECK_INTERVAL);
+        self.steps = self.steps.wrapping_add(1);
+
+        if !check_clock {
+            return None;
+        }
+
+        if INTERRUPTED.load(Ordering::Relaxed) {
+            return Some(Event::Stop(StopReason::Interrupted));
+        }
+
+        let time_elapsed = self.now.elapsed();
+
+        if let Some(t) = self.timeout
+            && time_elapsed >= t
+        {
+            return Some(Event::Stop(StopReason::Timeout));
+        }
+
+        if REPORT_REQUESTED.swap(false, Ordering::Relaxed) {
+            return Some(Event::Report(Progress::Elapsed(time_elapsed)));
+        }
+
+        if self.report_nodes.is_none() && time_elapsed >= self.time_threshold {
+            self.time_threshold += self.report_delta;
+
+            return Some(Event::Report(Progress::Elapsed(time_elapsed)));
+        }
+
+        None
+    }
+
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.now.elapsed()
+    }
+}
@@ -0,0 +1,714 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::time::Duration;
+
+use serde_json::{Value, json};
+
+use crate::branches::BranchTable;
+use crate::config::{Config, OutputFormat};
+use crate::profile::Profile;
+use crate::{OptionData, Statistics};
+
+thread_local! {
+    static CAPTURED: RefCell<Option<String>> = const { RefCell::new(None) };
+
+    // The solutions list of a JSON document is still open when a search stops,
+    // so a partial solution is kept until the statistics close it.
+    static PARTIAL: RefCell<Option<Value>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` collecting everything it prints to stdout through this module
+/// instead, so that instances solved in parallel do not mix their output. If
+/// `f` panics, what it printed is dropped and printing goes back to normal.
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, String) {
+    // Puts back the previous buffer, even when unwinding.
+    struct Restore(Option<String>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CAPTURED.replace(self.0.take());
+        }
+    }
+
+    let restore = Restore(CAPTURED.replace(Some(String::new())));
+    let result = f();
+    let captured = CAPTURED.take().unwrap();
+
+    drop(restore);
+
+    (result, captured)
+}
+
+fn emit(args: fmt::Arguments) {
+    CAPTURED.with_borrow_mut(|captured| match captured {
+        Some(buffer) => {
+            fmt::write(buffer, args).unwrap();
+            buffer.push('\n');
+        }
+        None => println!("{}", args),
+    });
+}
+
+pub fn print_header(format: OutputFormat) {
+    if format == OutputFormat::Json {
+        emit(format_args!("{{\"solutions\":["));
+    }
+}
+
+pub fn print_footer(format: OutputFormat) {
+    if format == OutputFormat::Json {
+        emit(format_args!("]}}"));
+    }
+}
+
+pub fn print_timeout(format: OutputFormat) {
+    if format == OutputFormat::Text {
+        emit(format_args!("TIMEOUT!"));
+    }
+}
+
+pub fn print_limit(format: OutputFormat) {
+    if format == OutputFormat::Text {
+        emit(format_args!("LIMIT REACHED!"));
+    }
+}
+
+pub fn print_interrupted(format: OutputFormat) {
+    if format == OutputFormat::Text {
+        emit(format_args!("INTERRUPTED!"));
+    }
+}
+
+/// An option of a solution, as chosen for the item the search branched on.
+pub struct ChosenOption<'a> {
+    pub option: usize,
+    pub items: Vec<&'a str>,
+    pub first: usize,
+    pub position: Option<usize>,
+    pub length: usize,
+    pub data: &'a OptionData,
+}
+
+impl ChosenOption<'_> {
+    // Items are listed starting from the one the search branched on.
+    pub(crate) fn to_text(&self) -> String {
+        let mut option_str = self.items[self.first..]
+            .iter()
+            .chain(&self.items[..self.first])
+            .copied()
+            .collect::<Vec<&str>>()
+            .join(" ");
+
+        if let Some(k) = self.position {
+            option_str.push_str(&format!(" ({} of {})", k, self.length));
+        } else {
+            option_str.push_str(&format!(" (? of {})", self.length));
+        }
+
+        if let Some(label) = &self.data.label {
+            option_str.push_str(&format!(" {}", label));
+        }
+
+        if let Some(payload) = &self.data.payload {
+            option_str.push_str(&format!(" {}", payload));
+        }
+
+        option_str
+    }
+
+    fn to_json(&self) -> Value {
+        let mut option = json!({
+            "option": self.option,
+            "line": self.data.line,
+            "items": self.items,
+        });
+
+        if let Some(label) = &self.data.label {
+            option["label"] = json!(label);
+        }
+
+        if let Some(payload) = &self.data.payload {
+            option["payload"] = payload.clone();
+        }
+
+        if let Some(cost) = self.data.cost {
+            option["cost"] = json!(cost);
+        }
+
+        option
+    }
+}
+
+pub fn print_solution(format: OutputFormat, index: usize, options: &[ChosenOption], first: bool) {
+    match format {
+        OutputFormat::Text => {
+            emit(format_args!("Solution {}:", index));
+
+            for option in options {
+                emit(format_args!(" {}", option.to_text()));
+            }
+        }
+        OutputFormat::Json => {
+            let options: Vec<Value> = options.iter().map(|o| o.to_json()).collect();
+            let record = json!({ "index": index, "options": options });
+
+            if first {
+                emit(format_args!("{}", record));
+            } else {
+                emit(format_args!(",{}", record));
+            }
+        }
+        OutputFormat::Ndjson => {
+            let options: Vec<Value> = options.iter().map(|o| o.to_json()).collect();
+            let record = json!({ "type": "solution", "index": index, "options": options });
+
+            emit(format_args!("{}", record));
+        }
+    }
+}
+
+/// Where a search was when it was stopped, and the deepest it went.
+pub struct Partial<'a> {
+    pub options: Vec<ChosenOption<'a>>,
+    pub explored: f64,
+    pub deepest: Vec<ChosenOption<'a>>,
+    /// Primary items left uncovered by the deepest partial solution.
+    pub uncovered: Vec<&'a str>,
+}
+
+pub fn print_partial(format: OutputFormat, partial: &Partial) {
+    if format == OutputFormat::Text {
+        emit(format_args!(
+            "Partial solution, {:.5} explored:",
+            partial.explored
+        ));
+
+        for option in &partial.options {
+            emit(format_args!(" {}", option.to_text()));
+        }
+
+        emit(format_args!(
+            "Deepest partial solution, with {} options:",
+            partial.deepest.len()
+        ));
+
+        for option in &partial.deepest {
+            emit(format_args!(" {}", option.to_text()));
+        }
+
+        if partial.uncovered.is_empty() {
+            emit(format_args!("It leaves no items uncovered."));
+        } else {
+            emit(format_args!(
+                "It leaves uncovered: {}",
+                partial.uncovered.join(" ")
+            ));
+        }
+
+        return;
+    }
+
+    let options: Vec<Value> = partial.options.iter().map(|o| o.to_json()).collect();
+    let deepest: Vec<Value> = partial.deepest.iter().map(|o| o.to_json()).collect();
+
+    let mut record = json!({
+        "options": options,
+        "explored": partial.explored,
+        "deepest": { "options": deepest, "uncovered": partial.uncovered },
+    });
+
+    match format {
+        OutputFormat::Text => unreachable!(),
+        OutputFormat::Json => PARTIAL.set(Some(record)),
+        OutputFormat::Ndjson => {
+            record["type"] = json!("partial");
+
+            emit(format_args!("{}", record));
+        }
+    }
+}
+
+/// Prints what is wrong with a solution read back by `--verify`. Valid
+/// solutions are only listed by the JSON formats.
+pub fn print_verification(format: OutputFormat, index: usize, violations: &[String], first: bool) {
+    let record = json!({
+        "index": index,
+        "valid": violations.is_empty(),
+        "violations": violations,
+    });
+
+    match format {
+        OutputFormat::Text => {
+            if !violations.is_empty() {
+                emit(format_args!("Solution {} is invalid:", index));
+
+                for violation in violations {
+                    emit(format_args!(" {}", violation));
+                }
+            }
+        }
+        OutputFormat::Json if first => emit(format_args!("{}", record)),
+        OutputFormat::Json => emit(format_args!(",{}", record)),
+        OutputFormat::Ndjson => {
+            let mut record = record;
+            record["type"] = json!("verification");
+
+            emit(format_args!("{}", record));
+        }
+    }
+}
+
+pub fn print_verification_summary(format: OutputFormat, valid: usize, invalid: usize) {
+    match format {
+        OutputFormat::Text => {
+            let total = valid + invalid;
+            let s = if total == 1 { "" } else { "s" };
+
+            emit(format_args!(
+                "Verified {} solution{}: {} valid and {} invalid.",
+                total, s, valid, invalid
+            ));
+        }
+        OutputFormat::Json => emit(format_args!(
+            "],\"statistics\":{}}}",
+            json!({ "valid": valid, "invalid": invalid })
+        )),
+        OutputFormat::Ndjson => emit(format_args!(
+            "{}",
+            json!({ "type": "statistics", "valid": valid, "invalid": invalid })
+        )),
+    }
+}
+
+pub fn print_statistics(config: &Config, stats: &Statistics, preprocess_time: Duration) {
+    let format = config.get_format();
+
+    if format == OutputFormat::Text {
+        print_text_statistics(stats, preprocess_time, config.is_verbose());
+
+        if let Some(profile) = &stats.profile {
+            print_text_profile(profile);
+        }
+
+        if let Some(branches) = &stats.branches {
+            print_text_branches(branches);
+        }
+
+        if config.is_unique() {
+            emit(format_args!("Verdict: {}.", stats.verdict().as_str()));
+        }
+
+        return;
+    }
+
+    let mut record = json!({
+        "solutions": stats.solution_count,
+        "preprocess_secs": preprocess_time.as_secs_f64(),
+        "dance_secs": stats.elapsed_time.as_secs_f64(),
+        "nodes": stats.visited_nodes,
+        "updates": stats.update_count,
+        "max_degree": stats.max_degree,
+        "max_level": stats.max_level,
+        "timeout": stats.timed_out,
+        "limit_reached": stats.limit_reached,
+        "interrupted": stats.interrupted,
+    });
+
+    if config.is_unique() {
+        record["verdict"] = json!(stats.verdict().as_str());
+    }
+
+    if let Some(mems) = stats.mems {
+        record["mems"] = json!(mems);
+    }
+
+    if let Some(profile) = &stats.profile {
+        record["profile"] = profile_to_json(profile);
+    }
+
+    if let Some(branches) = &stats.branches {
+        record["branches"] = branches_to_json(branches);
+    }
+
+    match format {
+        OutputFormat::Text => unreachable!(),
+        OutputFormat::Json => match PARTIAL.take() {
+            Some(partial) => emit(format_args!(
+                "],\"partial\":{},\"statistics\":{}}}",
+                partial, record
+            )),
+            None => emit(format_args!("],\"statistics\":{}}}", record)),
+        },
+        OutputFormat::Ndjson => {
+            record["type"] = json!("statistics");
+
+            emit(format_args!("{}", record));
+        }
+    }
+}
+
+fn print_text_statistics(stats: &Statistics, preprocess_time: Duration, verbose: bool) {
+    let solution_count = stats.solution_count;
+    let elapsed_time = stats.elapsed_time;
+
+    let s = if solution_count == 1 { "" } else { "s" };
+
+    if verbose {
+        let total_time = preprocess_time + elapsed_time;
+
+        emit(format_args!(
+            "The tree's maximum degree is {}, its depth is {}.",
+            stats.max_degree, stats.max_level,
+        ));
+
+        emit(format_args!(
+            "{:.5?} overall: {:.5?} processing input + {:.5?} dancing.",
+            total_time, preprocess_time, elapsed_time,
+        ));
+
+        if solution_count > 0 {
+            emit(format_args!(
+                "{:.5?} per solution.",
+                elapsed_time / (solution_count.try_into().unwrap())
+            ));
+        }
+    }
+
+    let mems = match stats.mems {
+        Some(mems) => format!(" and {} mems", mems),
+        None => String::new(),
+    };
+
+    emit(format_args!(
+        "Found {} solution{} in {:.5?} visiting {} nodes doing {} updates{}.",
+        solution_count, s, elapsed_time, stats.visited_nodes, stats.update_count, mems,
+    ));
+}
+
+// Items never branched on are left out.
+fn print_text_profile(profile: &Profile) {
+    emit(format_args!("Level      Nodes  Solutions  Avg degree"));
+
+    for (level, l) in profile.levels.iter().enumerate() {
+        emit(format_args!(
+            "{:>5} {:>10} {:>10} {:>11.3}",
+            level,
+            l.nodes,
+            l.solutions,
+            l.average_degree(),
+        ));
+    }
+
+    let width = profile
+        .items
+        .iter()
+        .filter(|item| item.chosen > 0)
+        .map(|item| item.name.len())
+        .max()
+        .unwrap_or(0)
+        .max(4);
+
+    emit(format_args!(
+        "{:<width$} {:>10} {:>10}",
+        "Item", "Chosen", "Dead ends"
+    ));
+
+    for item in profile.items.iter().filter(|item| item.chosen > 0) {
+        emit(format_args!(
+            "{:<width$} {:>10} {:>10}",
+            item.name, item.chosen, item.dead_ends,
+        ));
+    }
+}
+
+fn profile_to_json(profile: &Profile) -> Value {
+    let levels: Vec<Value> = profile
+        .levels
+        .iter()
+        .enumerate()
+        .map(|(level, l)| {
+            json!({
+                "level": level,
+                "nodes": l.nodes,
+                "solutions": l.solutions,
+                "average_degree": l.average_degree(),
+            })
+        })
+        .collect();
+
+    let items: Vec<Value> = profile
+        .items
+        .iter()
+        .filter(|item| item.chosen > 0)
+        .map(|item| {
+            json!({
+                "item": item.name,
+                "chosen": item.chosen,
+                "dead_ends": item.dead_ends,
+            })
+        })
+        .collect();
+
+    json!({ "levels": levels, "items": items })
+}
+
+// Options are indented by their level, under the branch they belong to.
+fn print_text_branches(branches: &BranchTable) {
+    emit(format_args!(
+        "Level  Solutions      Nodes           Time  Option"
+    ));
+
+    for branch in &branches.branches {
+        emit(format_args!(
+            "{:>5} {:>10} {:>10} {:>14}  {:indent$}{}",
+            branch.level,
+            branch.solutions,
+            branch.nodes,
+            format!("{:.5?}", branch.elapsed_time),
+            "",
+            branch.option_text,
+            indent = 2 * branch.level,
+        ));
+    }
+}
+
+fn branches_to_json(branches: &BranchTable) -> Value {
+    let branches: Vec<Value> = branches
+        .branches
+        .iter()
+        .map(|branch| {
+            json!({
+                "level": branch.level,
+                "parent": branch.parent,
+                "option": branch.option,
+                "option_text": branch.option_text,
+                "solutions": branch.solutions,
+                "nodes": branch.nodes,
+                "secs": branch.elapsed_time.as_secs_f64(),
+            })
+        })
+        .collect();
+
+    json!(branches)
+}
+
+pub fn print_batch_header(format: OutputFormat) {
+    if format == OutputFormat::Json {
+        println!("{{\"instances\":[");
+    }
+}
+
+pub fn print_batch_footer(format: OutputFormat) {
+    if format == OutputFormat::Json {
+        println!("]}}");
+    }
+}
+
+/// Prints the captured output of one instance of a batch, tagged with its
+/// index and name.
+pub fn print_instance(
+    format: OutputFormat,
+    index: usize,
+    name: Option<&str>,
+    captured: &str,
+    first: bool,
+) {
+    let tag = |record: &mut Value| {
+        record["instance"] = json!(index);
+        record["name"] = json!(name);
+    };
+
+    match format {
+        OutputFormat::Text => {
+            match name {
+                Some(name) => println!("Instance {} ({}):", index, name),
+                None => println!("Instance {}:", index),
+            }
+
+            print!("{}", captured);
+        }
+        OutputFormat::Json => {
+            let mut record: Value = serde_json::from_str(captured).unwrap();
+
+            tag(&mut record);
+
+            if first {
+                println!("{}", record);
+            } else {
+                println!(",{}", record);
+            }
+        }
+        OutputFormat::Ndjson => {
+            for line in captured.lines() {
+                let mut record: Value = serde_json::from_str(line).unwrap();
+
+                tag(&mut record);
+
+                println!("{}", record);
+            }
+        }
+    }
+}
+
+/// Prints the error that stopped one instance of a batch, tagged as by
+/// `print_instance`.
+pub fn print_instance_error(
+    format: OutputFormat,
+    index: usize,
+    name: Option<&str>,
+    message: &str,
+    first: bool,
+) {
+    match format {
+        OutputFormat::Text => {
+            match name {
+                Some(name) => println!("Instance {} ({}):", index, name),
+                None => println!("Instance {}:", index),
+            }
+
+            println!("Error: {}", message);
+        }
+        OutputFormat::Json => {
+            let record = json!({ "instance": index, "name": name, "error": message });
+
+            if first {
+                println!("{}", record);
+            } else {
+                println!(",{}", record);
+            }
+        }
+        OutputFormat::Ndjson => {
+            let record = json!({
+                "type": "error",
+                "instance": index,
+                "name": name,
+                "message": message,
+            });
+
+            println!("{}", record);
+        }
+    }
+}
+
+/// How far a search went when a progress report is printed.
+pub enum Progress {
+    Elapsed(Duration),
+    Nodes(usize),
+}
+
+/// Prints a progress report given, for each level of the search, the position
+/// of the option being tried and the number of options of its item.
+pub fn print_report(
+    progress: Progress,
+    solution_count: usize,
+    mems: Option<usize>,
+    branches: &[(Option<usize>, usize)],
+    level_limit: usize,
+) {
+    let level_limit = 3 * level_limit;
+
+    let mut branch_str = String::new();
+
+    for &(position, length) in branches {
+        let length_char = char::from_digit(length.try_into().unwrap(), 36).unwrap_or('*');
+
+        if let Some(k) = position {
+            let position_char = char::from_digit(k.try_into().unwrap(), 36).unwrap_or('*');
+
+            branch_str.push_str(&format!("{}{} ", position_char, length_char));
+        } else {
+            branch_str.push_str(&format!("?{} ", length_char));
+        }
+    }
+
+    let progress = match progress {
+        Progress::Elapsed(elapsed) => format!("{}s", elapsed.as_secs()),
+        Progress::Nodes(nodes) => format!("{} nodes", nodes),
+    };
+
+    if branch_str.len() > level_limit {
+        branch_str = branch_str.chars().take(level_limit).collect::<String>();
+        branch_str.push_str("...");
+    } else if !branch_str.is_empty() {
+        branch_str.pop();
+    }
+
+    let s = if solution_count == 1 { "" } else { "s" };
+    let explored = explored(branches);
+
+    let progress = match mems {
+        Some(mems) => format!("{}, {} mems", progress, mems),
+        None => progress,
+    };
+
+    if level_limit == 0 {
+        eprintln!(
+            "{}: {} solution{}, {:.5} explored",
+            progress, solution_count, s, explored,
+        );
+    } else {
+        eprintln!(
+            "{}: {} solution{}, {}, {:.5} explored",
+            progress, solution_count, s, branch_str, explored,
+        );
+    }
+}
+
+/// Estimates the fraction of the search tree already explored, assuming every
+/// subtree of a level is as large as the others.
+pub fn explored(branches: &[(Option<usize>, usize)]) -> f64 {
+    let mut explored = 0.0;
+    let mut d = 1.0;
+
+    for &(position, length) in branches {
+        d *= length as f64;
+
+        if let Some(k) = position {
+            explored += ((k - 1) as f64) / d;
+        }
+    }
+
+    explored
+}
+
+pub fn print_warning(message: &str) {
+    eprintln!("Warning: {}", message);
+}
+
+/// Traces the option tried at `level`.
+pub fn print_choice(level: usize, option: &ChosenOption) {
+    eprintln!("L{}: {}", level, option.to_text());
+}
+
+pub fn print_dead_end(level: usize, item: &str) {
+    eprintln!("L{}: {} has no options left", level, item);
+}
+
+pub fn print_traced_solution(level: usize, index: usize) {
+    eprintln!("L{}: solution {}", level, index);
+}
+
+/// Prints the options chosen before `level` and the primary items still to
+/// cover with their numbers of options.
+pub fn print_state(level: usize, options: &[ChosenOption], items: &[(&str, usize)]) {
+    eprintln!("Current state (level {}):", level);
+
+    for option in options {
+        eprintln!(" {}", option.to_text());
+    }
+
+    eprintln!(" Items left: {}", format_lengths(items));
+}
+
+/// Prints the number of options of every item.
+pub fn print_item_totals(when: &str, totals: &[(&str, usize)]) {
+    eprintln!("Item totals {}: {}", when, format_lengths(totals));
+}
+
+fn format_lengths(items: &[(&str, usize)]) -> String {
+    items
+        .iter()
+        .map(|(name, length)| format!("{}({})", name, length))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
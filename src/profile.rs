@@ -0,0 +1,84 @@
+/// Nodes visited at one level of the search tree.
+#[derive(Clone, Default)]
+pub struct LevelProfile {
+    pub nodes: usize,
+    pub solutions: usize,
+    /// Sum of the degrees of the items branched on at this level.
+    pub degrees: usize,
+}
+
+impl LevelProfile {
+    /// Returns the average number of options of the items branched on.
+    pub fn average_degree(&self) -> f64 {
+        match self.nodes - self.solutions {
+            0 => 0.0,
+            branches => self.degrees as f64 / branches as f64,
+        }
+    }
+}
+
+/// How the search used one item.
+#[derive(Clone)]
+pub struct ItemProfile {
+    pub name: String,
+    /// Times the search branched on the item.
+    pub chosen: usize,
+    /// Times it was chosen with no options left, ending the branch.
+    pub dead_ends: usize,
+}
+
+/// Per-level and per-item counts gathered by a search with `--profile`, in
+/// the spirit of the profile shown by Knuth's programs.
+pub struct Profile {
+    pub levels: Vec<LevelProfile>,
+    /// Indexed by item, starting from 0 for the first one.
+    pub items: Vec<ItemProfile>,
+}
+
+impl Profile {
+    /// Takes the names of the items, with an unused one at index 0 as the
+    /// solvers keep them.
+    pub(crate) fn new(item_names: &[String]) -> Self {
+        Profile {
+            levels: Vec::new(),
+            items: item_names[1..]
+                .iter()
+                .map(|name| ItemProfile {
+                    name: name.clone(),
+                    chosen: 0,
+                    dead_ends: 0,
+                })
+                .collect(),
+        }
+    }
+
+    pub(crate) fn add_branch(&mut self, level: usize, i: usize, degree: usize) {
+        let l = self.get_level(level);
+
+        l.nodes += 1;
+        l.degrees += degree;
+
+        let item = &mut self.items[i - 1];
+
+        item.chosen += 1;
+
+        if degree == 0 {
+            item.dead_ends += 1;
+        }
+    }
+
+    pub(crate) fn add_solution(&mut self, level: usize) {
+        let l = self.get_level(level);
+
+        l.nodes += 1;
+        l.solutions += 1;
+    }
+
+    fn get_level(&mut self, level: usize) -> &mut LevelProfile {
+        if self.levels.len() <= level {
+            self.levels.resize(level + 1, LevelProfile::default());
+        }
+
+        &mut self.levels[level]
+    }
+}
@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+use std::hash::Hasher;
+use std::io::{self, Read, Write};
+
+use crate::DancingLinks;
+use crate::config::Config;
+use crate::utils::*;
+
+const MAGIC: &[u8; 8] = b"DLXSNAP\0";
+const VERSION: u32 = 1;
+
+pub fn save<W: Write>(dlx: &DancingLinks, mut writer: W) -> io::Result<()> {
+    let mut payload = Vec::new();
+
+    write_u32(&mut payload, dlx.primary);
+    write_u32(&mut payload, dlx.secondary);
+
+    write_u32(&mut payload, dlx.item_header.len());
+
+    for i in 0..dlx.item_header.len() {
+        if i == 0 || i == dlx.item_header.len() - 1 {
+            write_optional_str(&mut payload, None);
+        } else {
+            write_optional_str(&mut payload, Some(&dlx.item_names[i]));
+        }
+
+        write_u32(&mut payload, dlx.get_length(i));
+        write_u32(&mut payload, dlx.get_left(i));
+        write_u32(&mut payload, dlx.get_right(i));
+    }
+
+    write_u32(&mut payload, dlx.node_list.len());
+
+    for p in 0..dlx.node_list.len() {
+        payload.extend_from_slice(&(dlx.get_top(p) as i32).to_le_bytes());
+        write_u32(&mut payload, dlx.get_up(p));
+        write_u32(&mut payload, dlx.get_down(p));
+    }
+
+    write_u32(&mut payload, dlx.option_data.len());
+
+    for data in &dlx.option_data {
+        write_optional_u64(&mut payload, data.line);
+        write_optional_str(&mut payload, data.label.as_deref());
+        write_optional_str(
+            &mut payload,
+            data.payload.as_ref().map(|p| p.to_string()).as_deref(),
+        );
+        write_optional_i64(&mut payload, data.cost);
+    }
+
+    for &active in dlx.option_active.iter().chain(&dlx.item_active[1..]) {
+        payload.push(active as u8);
+    }
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&(payload.len() as u64).to_le_bytes())?;
+    writer.write_all(&checksum(&payload).to_le_bytes())?;
+    writer.write_all(&payload)?;
+
+    writer.flush()
+}
+
+pub fn load<R: Read>(config: &Config, mut reader: R) -> Result<DancingLinks, String> {
+    let mut bytes = Vec::new();
+
+    reader
+        .read_to_end(&mut bytes)
+        .map_err(|err| format!("Failed to read snapshot: {}", err))?;
+
+    let mut header = Reader {
+        bytes: &bytes,
+        pos: 0,
+    };
+
+    if header.take(MAGIC.len())? != MAGIC {
+        return Err(String::from("File is not a dlx snapshot."));
+    }
+
+    let version = u32::from_le_bytes(header.take(4)?.try_into().unwrap());
+
+    if version != VERSION {
+        return Err(format!(
+            "Snapshot version {} is not supported (expected {}).",
+            version, VERSION,
+        ));
+    }
+
+    let length = header.read_u64()?;
+    let expected = u64::from_le_bytes(header.take(8)?.try_into().unwrap());
+
+    let payload = &bytes[header.pos..];
+
+    if payload.len() != length {
+        return Err(String::from("Snapshot size does not match its header."));
+    }
+
+    if checksum(payload) != expected {
+        return Err(String::from("Snapshot checksum does not match."));
+    }
+
+    let mut r = Reader {
+        bytes: payload,
+        pos: 0,
+    };
+
+    let primary = r.read_u32()?;
+    let secondary = r.read_u32()?;
+
+    let header_len = r.read_u32()?;
+
+    if header_len != primary + secondary + 2 {
+        return Err(String::from("Snapshot item count is inconsistent."));
+    }
+
+    let mut item_header = ItemHeader::with_capacity(header_len);
+    let mut item_names = Vec::with_capacity(header_len);
+    let mut item_index = HashMap::with_capacity_and_hasher(header_len, FnvBuildHasher::default());
+
+    for i in 0..header_len {
+        let name = r.read_optional_str()?.unwrap_or_default();
+
+        if !name.is_empty() {
+            item_index.insert(name.clone(), i);
+        }
+
+        let length = r.read_u32()?;
+        let left = r.read_u32()?;
+        let right = r.read_u32()?;
+
+        if left >= header_len || right >= header_len {
+            return Err(String::from("Snapshot links are out of range."));
+        }
+
+        item_header.push(left, right);
+        item_header.length[i] = length as u32;
+        item_names.push(name);
+    }
+
+    let list_len = r.read_u32()?;
+
+    let mut node_list = NodeList::with_capacity(list_len);
+
+    for _ in 0..list_len {
+        let top = i32::from_le_bytes(r.take(4)?.try_into().unwrap()) as isize;
+        let up = r.read_u32()?;
+        let down = r.read_u32()?;
+
+        if top >= header_len as isize
+            || top == i32::MIN as isize
+            || up >= list_len
+            || down >= list_len
+        {
+            return Err(String::from("Snapshot links are out of range."));
+        }
+
+        node_list.push(top, up, down);
+    }
+
+    let option_count = r.read_u32()?;
+
+    let mut option_data = Vec::with_capacity(option_count);
+
+    for _ in 0..option_count {
+        let line = r.read_optional_u64()?;
+        let label = r.read_optional_str()?;
+
+        let payload = match r.read_optional_str()? {
+            Some(p) => Some(serde_json::from_str(&p).map_err(|err| err.to_string())?),
+            None => None,
+        };
+
+        let cost = r.read_optional_i64()?;
+
+        option_data.push(OptionData {
+            line,
+            label,
+            payload,
+            cost,
+        });
+    }
+
+    if item_index.len() != primary + secondary {
+        return Err(String::from("Snapshot item names are not unique."));
+    }
+
+    let mut option_active = vec![true; option_count];
+    let mut item_active = vec![true; primary + secondary + 1];
+
+    for active in option_active.iter_mut().chain(&mut item_active[1..]) {
+        *active = r.read_flag()?;
+    }
+
+    Ok(DancingLinks {
+        item_header,
+        node_list,
+        item_names,
+        option_data,
+        item_index,
+        option_marks: vec![0; (primary + secondary + 1).div_ceil(64)],
+        option_active,
+        item_active,
+        mems: MemCounter::default(),
+        primary,
+        secondary,
+        config: config.clone(),
+    })
+}
+
+// 64-bit FNV-1a, enough to catch truncated or corrupted files.
+fn checksum(bytes: &[u8]) -> u64 {
+    let mut hasher = FnvHasher::default();
+
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+fn write_u32(buffer: &mut Vec<u8>, n: usize) {
+    let n: u32 = n.try_into().expect("Instance is too large for a snapshot.");
+
+    buffer.extend_from_slice(&n.to_le_bytes());
+}
+
+fn write_u64(buffer: &mut Vec<u8>, n: usize) {
+    buffer.extend_from_slice(&(n as u64).to_le_bytes());
+}
+
+fn write_optional_u64(buffer: &mut Vec<u8>, n: Option<usize>) {
+    match n {
+        Some(n) => {
+            buffer.push(1);
+            write_u64(buffer, n);
+        }
+        None => buffer.push(0),
+    }
+}
+
+fn write_optional_i64(buffer: &mut Vec<u8>, n: Option<i64>) {
+    match n {
+        Some(n) => {
+            buffer.push(1);
+            buffer.extend_from_slice(&n.to_le_bytes());
+        }
+        None => buffer.push(0),
+    }
+}
+
+fn write_optional_str(buffer: &mut Vec<u8>, s: Option<&str>) {
+    match s {
+        Some(s) => {
+            buffer.push(1);
+            write_u64(buffer, s.len());
+            buffer.extend_from_slice(s.as_bytes());
+        }
+        None => buffer.push(0),
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() - self.pos < n {
+            return Err(String::from("Snapshot is truncated."));
+        }
+
+        self.pos += n;
+
+        Ok(&self.bytes[self.pos - n..self.pos])
+    }
+
+    fn read_u64(&mut self) -> Result<usize, String> {
+        let n = u64::from_le_bytes(self.take(8)?.try_into().unwrap());
+
+        n.try_into()
+            .map_err(|_| String::from("Snapshot value does not fit in memory."))
+    }
+
+    fn read_u32(&mut self) -> Result<usize, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()) as usize)
+    }
+
+    fn read_flag(&mut self) -> Result<bool, String> {
+        match self.take(1)?[0] {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(String::from("Snapshot is corrupted.")),
+        }
+    }
+
+    fn read_optional_u64(&mut self) -> Result<Option<usize>, String> {
+        if self.read_flag()? {
+            Ok(Some(self.read_u64()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn read_optional_i64(&mut self) -> Result<Option<i64>, String> {
+        if self.read_flag()? {
+            Ok(Some(i64::from_le_bytes(self.take(8)?.try_into().unwrap())))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn read_optional_str(&mut self) -> Result<Option<String>, String> {
+        if !self.read_flag()? {
+            return Ok(None);
+        }
+
+        let len = self.read_u64()?;
+        let bytes = self.take(len)?;
+
+        match String::from_utf8(bytes.to_vec()) {
+            Ok(s) => Ok(Some(s)),
+            Err(_) => Err(String::from("Snapshot holds an invalid string.")),
+        }
+    }
+}
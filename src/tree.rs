@@ -0,0 +1,181 @@
+use std::io::{self, Write};
+
+use serde_json::{Value, json};
+
+use crate::config::TreeFormat;
+
+/// What a node of the search tree turned out to be.
+pub(crate) enum NodeKind {
+    /// The search branched on `item`, which had `degree` options left.
+    Branch {
+        item: String,
+        degree: usize,
+    },
+    Solution,
+}
+
+struct TreeNode {
+    parent: Option<usize>,
+    // The option tried at the parent to get here, by number and as text.
+    option: Option<(usize, String)>,
+    kind: NodeKind,
+    // Whether some of its children were left out by the limits.
+    truncated: bool,
+}
+
+/// Search tree recorded by `DancingLinks::dance_with_tree`, up to a given
+/// depth and number of nodes. Nodes are kept in the order they are visited.
+pub struct SearchTree {
+    nodes: Vec<TreeNode>,
+    // Recorded node of each level of the current path, if any.
+    path: Vec<Option<usize>>,
+    max_depth: Option<usize>,
+    max_nodes: Option<usize>,
+}
+
+impl SearchTree {
+    pub fn new(max_depth: Option<usize>, max_nodes: Option<usize>) -> Self {
+        SearchTree {
+            nodes: Vec::new(),
+            path: Vec::new(),
+            max_depth,
+            max_nodes,
+        }
+    }
+
+    /// Records the node visited at `level`. `kind` and `option`, the option
+    /// tried at its parent, are only called if the node is kept.
+    pub(crate) fn add_node(
+        &mut self,
+        level: usize,
+        kind: impl FnOnce() -> NodeKind,
+        option: impl FnOnce() -> (usize, String),
+    ) {
+        self.path.truncate(level);
+
+        let parent = match level {
+            0 => None,
+            _ => match self.path[level - 1] {
+                Some(parent) => Some(parent),
+                None => {
+                    self.path.push(None);
+
+                    return;
+                }
+            },
+        };
+
+        if self.max_depth.is_some_and(|d| level > d)
+            || self.max_nodes.is_some_and(|n| self.nodes.len() >= n)
+        {
+            if let Some(parent) = parent {
+                self.nodes[parent].truncated = true;
+            }
+
+            self.path.push(None);
+
+            return;
+        }
+
+        self.path.push(Some(self.nodes.len()));
+
+        self.nodes.push(TreeNode {
+            parent,
+            option: parent.map(|_| option()),
+            kind: kind(),
+            truncated: false,
+        });
+    }
+
+    pub fn get_node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn write<W: Write>(&self, format: TreeFormat, writer: W) -> io::Result<()> {
+        match format {
+            TreeFormat::Dot => self.write_dot(writer),
+            TreeFormat::Json => self.write_json(writer),
+        }
+    }
+
+    /// Writes the tree for Graphviz. Branching nodes show their item and
+    /// degree, dead ends are red, solutions green and nodes with children
+    /// left out are dashed.
+    pub fn write_dot<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "digraph search {{")?;
+        writeln!(writer, "  node [shape=box];")?;
+
+        for (k, node) in self.nodes.iter().enumerate() {
+            let mut attributes = match &node.kind {
+                NodeKind::Branch { item, degree: 0 } => {
+                    format!("label=\"{} (0)\", color=red", escape(item))
+                }
+                NodeKind::Branch { item, degree } => {
+                    format!("label=\"{} ({})\"", escape(item), degree)
+                }
+                NodeKind::Solution => String::from("label=\"solution\", color=green"),
+            };
+
+            if node.truncated {
+                attributes.push_str(", style=dashed");
+            }
+
+            writeln!(writer, "  n{} [{}];", k, attributes)?;
+
+            if let (Some(parent), Some((_, option))) = (node.parent, &node.option) {
+                writeln!(
+                    writer,
+                    "  n{} -> n{} [label=\"{}\"];",
+                    parent,
+                    k,
+                    escape(option)
+                )?;
+            }
+        }
+
+        writeln!(writer, "}}")?;
+
+        writer.flush()
+    }
+
+    /// Writes the tree as a JSON list of nodes, each with the index of its
+    /// parent, so that it can be rebuilt by external viewers.
+    pub fn write_json<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let nodes: Vec<Value> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(k, node)| {
+                let mut record = json!({
+                    "id": k,
+                    "parent": node.parent,
+                    "truncated": node.truncated,
+                });
+
+                if let Some((option, text)) = &node.option {
+                    record["option"] = json!(option);
+                    record["option_text"] = json!(text);
+                }
+
+                match &node.kind {
+                    NodeKind::Branch { item, degree } => {
+                        record["kind"] = json!(if *degree == 0 { "dead_end" } else { "branch" });
+                        record["item"] = json!(item);
+                        record["degree"] = json!(degree);
+                    }
+                    NodeKind::Solution => record["kind"] = json!("solution"),
+                }
+
+                record
+            })
+            .collect();
+
+        writeln!(writer, "{}", json!({ "nodes": nodes }))?;
+
+        writer.flush()
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
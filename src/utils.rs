@@ -1,66 +1,207 @@
-pub struct Record {
-    pub name: Option<String>,
-    pub length: usize,
-    pub left: usize,
-    pub right: usize,
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hash, Hasher};
+
+use serde_json::Value;
+
+#[derive(Clone, Default)]
+pub struct OptionData {
+    pub line: Option<usize>,
+    pub label: Option<String>,
+    pub payload: Option<Value>,
+    pub cost: Option<i64>,
+}
+
+/// Item records stored column by column so that the search only touches
+/// the fields it needs. Names are kept apart in `DancingLinks`.
+pub struct ItemHeader {
+    pub length: Vec<u32>,
+    pub left: Vec<u32>,
+    pub right: Vec<u32>,
 }
 
-pub struct Node {
-    pub top: isize,
-    pub up: usize,
-    pub down: usize,
+/// Nodes stored column by column. Spacers have a nonpositive `top`.
+pub struct NodeList {
+    pub top: Vec<i32>,
+    pub up: Vec<u32>,
+    pub down: Vec<u32>,
 }
 
-impl Record {
-    pub fn new(name: &str, i: usize) -> Self {
-        if i == 0 {
-            panic!("Record cannot be initialized if index is 0.");
+impl ItemHeader {
+    pub fn with_capacity(capacity: usize) -> Self {
+        ItemHeader {
+            length: Vec::with_capacity(capacity),
+            left: Vec::with_capacity(capacity),
+            right: Vec::with_capacity(capacity),
         }
+    }
+
+    pub fn push(&mut self, left: usize, right: usize) {
+        self.length.push(0);
+        self.left.push(to_index(left));
+        self.right.push(to_index(right));
+    }
+
+    pub fn len(&self) -> usize {
+        self.length.len()
+    }
+}
 
-        Record {
-            name: Some(String::from(name)),
-            length: 0,
-            left: i - 1,
-            right: i + 1,
+impl NodeList {
+    pub fn with_capacity(capacity: usize) -> Self {
+        NodeList {
+            top: Vec::with_capacity(capacity),
+            up: Vec::with_capacity(capacity),
+            down: Vec::with_capacity(capacity),
         }
     }
 
-    pub fn new_unnamed(left: usize, right: usize) -> Self {
-        Record {
-            name: None,
-            length: 0,
-            left,
-            right,
+    pub fn push(&mut self, top: isize, up: usize, down: usize) {
+        self.top.push(
+            top.try_into()
+                .expect("Instances are limited to 2^31 items and options."),
+        );
+        self.up.push(to_index(up));
+        self.down.push(to_index(down));
+    }
+
+    pub fn push_spacer(&mut self) {
+        self.push(0, 0, 0);
+    }
+
+    pub fn push_header(&mut self, i: usize) {
+        self.push(0, i, i);
+    }
+
+    pub fn len(&self) -> usize {
+        self.top.len()
+    }
+}
+
+fn to_index(i: usize) -> u32 {
+    i.try_into().expect("Instances are limited to 2^32 nodes.")
+}
+
+/// Memory accesses made by the search, counted as in Knuth's programs with one
+/// mem per node or item record read or written. It only counts with the `mems`
+/// feature and is otherwise empty, so the search does not pay for it.
+#[derive(Clone, Copy, Default)]
+pub struct MemCounter {
+    #[cfg(feature = "mems")]
+    count: usize,
+}
+
+impl MemCounter {
+    #[inline(always)]
+    pub fn add(&mut self, _mems: usize) {
+        #[cfg(feature = "mems")]
+        {
+            self.count += _mems;
         }
     }
 
-    pub fn add_node(&mut self) {
-        self.length += 1;
+    pub fn get(&self) -> Option<usize> {
+        #[cfg(feature = "mems")]
+        return Some(self.count);
+
+        #[cfg(not(feature = "mems"))]
+        None
     }
+}
+
+/// FNV-1a, much cheaper than the default hasher for short item names.
+pub struct FnvHasher(u64);
 
-    pub fn remove_node(&mut self) {
-        self.length -= 1;
+pub type FnvBuildHasher = BuildHasherDefault<FnvHasher>;
+
+impl Default for FnvHasher {
+    fn default() -> Self {
+        FnvHasher(0xcbf29ce484222325)
     }
 }
 
-impl Node {
-    pub fn new(top: isize, up: usize, down: usize) -> Self {
-        Node { top, up, down }
+impl Hasher for FnvHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
     }
+}
+
+/// Looks for input that is valid but likely a mistake in the options of an
+/// instance, numbered from 1, with items numbered from 1 and the first
+/// `primary` of them primary.
+pub fn get_warnings(
+    primary: usize,
+    item_names: &[String],
+    option_items: &[Vec<usize>],
+    option_data: &[OptionData],
+) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    let mut lengths = vec![0; item_names.len()];
+    // Options by the hash of their sorted items, compared in full on a match.
+    let mut seen: HashMap<u64, Vec<usize>, FnvBuildHasher> = HashMap::default();
+
+    let sorted = |items: &[usize]| {
+        let mut key = items.to_vec();
+        key.sort_unstable();
+
+        key
+    };
 
-    pub fn new_spacer() -> Self {
-        Node {
-            top: 0,
-            up: 0,
-            down: 0,
+    for (k, items) in option_items.iter().enumerate() {
+        let describe = |option: usize| match option_data[option - 1].line {
+            Some(line) => format!("Option {} (line {})", option, line),
+            None => format!("Option {}", option),
+        };
+
+        for &i in items {
+            lengths[i] += 1;
+        }
+
+        if items.iter().all(|&i| i > primary) {
+            warnings.push(format!(
+                "{} has no primary items, so it is never chosen.",
+                describe(k + 1),
+            ));
+        }
+
+        let key = sorted(items);
+
+        let mut hasher = FnvHasher::default();
+        key.hash(&mut hasher);
+
+        let same = seen.entry(hasher.finish()).or_default();
+
+        match same.iter().find(|&&o| sorted(&option_items[o - 1]) == key) {
+            Some(&o) => warnings.push(format!(
+                "{} has the same items as option {}.",
+                describe(k + 1),
+                o,
+            )),
+            None => same.push(k + 1),
         }
     }
 
-    pub fn new_header(i: usize) -> Self {
-        Node {
-            top: 0,
-            up: i,
-            down: i,
+    for i in 1..item_names.len() {
+        if lengths[i] > 0 {
+            continue;
+        }
+
+        if i <= primary {
+            warnings.push(format!(
+                "Primary item {} is in no option, so there are no solutions.",
+                item_names[i],
+            ));
+        } else {
+            warnings.push(format!("Secondary item {} is in no option.", item_names[i]));
         }
     }
+
+    warnings
 }
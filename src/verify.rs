@@ -0,0 +1,244 @@
+use std::collections::HashSet;
+use std::io::BufRead;
+
+use serde_json::Value;
+
+use crate::input::Instance;
+
+/// A solution read back from a file, its options given by their item names.
+pub struct SolutionRecord {
+    pub index: usize,
+    pub options: Vec<Vec<String>>,
+}
+
+/// Reads the solutions printed by the solver in any output format, skipping
+/// everything else it prints. A file with no solutions is an error, as when
+/// the solver was run without printing them.
+pub fn read_solutions<R: BufRead>(mut reader: R) -> Result<Vec<SolutionRecord>, String> {
+    let mut content = String::new();
+
+    reader
+        .read_to_string(&mut content)
+        .map_err(|err| err.to_string())?;
+
+    let solutions = if content.trim_start().starts_with('{') {
+        read_json_solutions(&content)?
+    } else {
+        read_text_solutions(&content)
+    };
+
+    if solutions.is_empty() {
+        return Err(String::from(
+            "The solution file has no solutions, print them with -i or -f.",
+        ));
+    }
+
+    Ok(solutions)
+}
+
+fn read_text_solutions(content: &str) -> Vec<SolutionRecord> {
+    let mut solutions = Vec::new();
+    let mut current: Option<SolutionRecord> = None;
+
+    for line in content.lines() {
+        if let Some(header) = line.strip_prefix("Solution ")
+            && let Some(Ok(index)) = header.strip_suffix(':').map(str::parse)
+        {
+            solutions.extend(current.take());
+            current = Some(SolutionRecord {
+                index,
+                options: Vec::new(),
+            });
+        } else if line.starts_with(' ') {
+            if let Some(solution) = &mut current {
+                solution.options.push(parse_text_option(line));
+            }
+        } else {
+            // Anything else, such as a partial solution, ends a solution.
+            solutions.extend(current.take());
+        }
+    }
+
+    solutions.extend(current);
+
+    solutions
+}
+
+// Options are printed as their items followed by "(k of n)" and then by their
+// label and payload, if any.
+fn parse_text_option(line: &str) -> Vec<String> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+
+    let end = (0..tokens.len())
+        .find(|&p| {
+            tokens[p].starts_with('(')
+                && tokens.get(p + 1) == Some(&"of")
+                && tokens.get(p + 2).is_some_and(|t| t.ends_with(')'))
+        })
+        .unwrap_or(tokens.len());
+
+    tokens[..end].iter().map(|t| t.to_string()).collect()
+}
+
+// Both a single JSON document and NDJSON records are accepted.
+fn read_json_solutions(content: &str) -> Result<Vec<SolutionRecord>, String> {
+    if let Ok(document) = serde_json::from_str::<Value>(content) {
+        let records = match document.get("solutions") {
+            Some(Value::Array(records)) => records,
+            _ => return Err(String::from("No \"solutions\" list in the JSON document.")),
+        };
+
+        return records
+            .iter()
+            .enumerate()
+            .map(|(k, record)| json_to_solution(record, k + 1))
+            .collect();
+    }
+
+    let mut solutions = Vec::new();
+
+    for (k, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record: Value = serde_json::from_str(line)
+            .map_err(|err| format!("Invalid JSON on line {}: {}.", k + 1, err))?;
+
+        if record["type"] == "solution" {
+            solutions.push(json_to_solution(&record, solutions.len() + 1)?);
+        }
+    }
+
+    Ok(solutions)
+}
+
+fn json_to_solution(record: &Value, position: usize) -> Result<SolutionRecord, String> {
+    let index = record["index"].as_u64().map_or(position, |i| i as usize);
+
+    let options = match &record["options"] {
+        Value::Array(options) => options,
+        _ => return Err(format!("Solution {index} has no \"options\" list.")),
+    };
+
+    let options = options
+        .iter()
+        .map(|option| {
+            let items = match option {
+                Value::Array(items) => items,
+                _ => match &option["items"] {
+                    Value::Array(items) => items,
+                    _ => return Err(format!("An option of solution {index} has no items.")),
+                },
+            };
+
+            items
+                .iter()
+                .map(|item| match item {
+                    Value::String(name) => Ok(name.clone()),
+                    _ => Err(format!(
+                        "Solution {index} has an item that is not a string."
+                    )),
+                })
+                .collect()
+        })
+        .collect::<Result<_, String>>()?;
+
+    Ok(SolutionRecord { index, options })
+}
+
+/// Checks solutions against the options of an instance as they were read,
+/// without going through any search structure.
+pub struct Verifier<'a> {
+    instance: &'a Instance,
+    // Sorted items of every option.
+    options: HashSet<Vec<usize>>,
+}
+
+impl<'a> Verifier<'a> {
+    pub fn new(instance: &'a Instance) -> Self {
+        let options = instance
+            .get_option_items()
+            .iter()
+            .map(|items| {
+                let mut items = items.clone();
+                items.sort_unstable();
+
+                items
+            })
+            .collect();
+
+        Verifier { instance, options }
+    }
+
+    /// Returns every way in which `options` fails to be an exact cover: items
+    /// or options not in the instance, primary items not covered exactly once
+    /// and secondary items covered more than once. Options are numbered from
+    /// 1 in the order they are given.
+    pub fn check(&self, options: &[Vec<String>]) -> Vec<String> {
+        let instance = self.instance;
+
+        let mut violations = Vec::new();
+        let mut covered_by = vec![Vec::new(); instance.get_item_count() + 1];
+
+        for (k, names) in options.iter().enumerate() {
+            let k = k + 1;
+
+            let mut items = Vec::with_capacity(names.len());
+
+            for name in names {
+                match instance.find_item(name) {
+                    Some(i) if items.contains(&i) => {
+                        violations.push(format!("Option {k} repeats item \"{name}\"."));
+                    }
+                    Some(i) => items.push(i),
+                    None => violations.push(format!("Option {k} has unknown item \"{name}\".")),
+                }
+            }
+
+            if items.len() == names.len() {
+                let mut key = items.clone();
+                key.sort_unstable();
+
+                if !self.options.contains(&key) {
+                    violations.push(format!(
+                        "Option {k} ({}) is not an option of the instance.",
+                        names.join(" ")
+                    ));
+                }
+            }
+
+            for i in items {
+                covered_by[i].push(k);
+            }
+        }
+
+        for (i, by) in covered_by.iter().enumerate().skip(1) {
+            let name = instance.get_item_name(i);
+            let list = || {
+                by.iter()
+                    .map(|k| k.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+
+            if i <= instance.get_primary() {
+                match by.len() {
+                    0 => violations.push(format!("Primary item \"{name}\" is not covered.")),
+                    1 => {}
+                    _ => violations.push(format!(
+                        "Primary item \"{name}\" is covered by options {}.",
+                        list()
+                    )),
+                }
+            } else if by.len() > 1 {
+                violations.push(format!(
+                    "Secondary item \"{name}\" is covered by options {}.",
+                    list()
+                ));
+            }
+        }
+
+        violations
+    }
+}
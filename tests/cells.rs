@@ -0,0 +1,44 @@
+use dlx::DancingLinks;
+use dlx::cells::DancingCells;
+use dlx::config::Config;
+use dlx::output;
+
+// Builds the 4-queens problem, whose first option is r1 c1 a2 b0.
+fn queens4(config: &Config) -> DancingLinks {
+    let mut dlx = DancingLinks::new(
+        config,
+        &["r1", "r2", "r3", "r4", "c1", "c2", "c3", "c4"],
+        &[
+            "a2", "a3", "a4", "a5", "a6", "a7", "a8", "b-3", "b-2", "b-1", "b0", "b1", "b2", "b3",
+        ],
+    );
+
+    for r in 1..=4 {
+        for c in 1..=4 {
+            dlx.add_option(&format!("r{r} c{c} a{} b{}", r + c, r - c));
+        }
+    }
+
+    dlx
+}
+
+#[test]
+fn forced_option_is_counted_among_active_options() {
+    let config = Config::build(&["dlx", "-i", "1"].map(String::from)).unwrap();
+
+    let mut dlx = queens4(&config);
+    dlx.deactivate_option(1);
+
+    let mut dc = DancingCells::from_links(&dlx);
+
+    let (stats, printed) = output::capture(|| dc.dance_with(&[2]));
+    let (dlx_stats, dlx_printed) = output::capture(|| dlx.dance_with(&[2]));
+
+    assert_eq!(stats.solution_count, 1);
+    assert_eq!(dlx_stats.solution_count, 1);
+    assert!(printed.contains(" r1 c2 a3 b-1 (1 of 3)\n"), "{printed}");
+    assert!(
+        dlx_printed.contains(" r1 c2 a3 b-1 (1 of 3)\n"),
+        "{dlx_printed}"
+    );
+}
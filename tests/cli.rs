@@ -0,0 +1,779 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+use serde_json::{Value, json};
+
+// The 4-queens problem, which has two solutions.
+const QUEENS4: &str = "r1 r2 r3 r4 c1 c2 c3 c4 | a2 a3 a4 a5 a6 a7 a8 b-3 b-2 b-1 b0 b1 b2 b3
+r1 c1 a2 b0
+r1 c2 a3 b-1
+r1 c3 a4 b-2
+r1 c4 a5 b-3
+r2 c1 a3 b1
+r2 c2 a4 b0
+r2 c3 a5 b-1
+r2 c4 a6 b-2
+r3 c1 a4 b2
+r3 c2 a5 b1
+r3 c3 a6 b0
+r3 c4 a7 b-1
+r4 c1 a5 b3
+r4 c2 a6 b2
+r4 c3 a7 b1
+r4 c4 a8 b0
+";
+
+fn run(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_dlx"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn unique_stopped_by_max_solutions_is_unknown() {
+    for engine in ["dlx", "bitset", "cells"] {
+        let output = run(&["-u", "-M", "1", "-E", engine], QUEENS4);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+
+        assert!(stdout.contains("Verdict: unknown."), "{engine}: {stdout}");
+        assert_eq!(output.status.code(), Some(3), "{engine}");
+    }
+}
+
+#[test]
+fn limits_as_large_as_the_tree_finish_the_search() {
+    for engine in ["dlx", "bitset", "cells"] {
+        let output = run(&["-E", engine], QUEENS4);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+
+        // "Found 2 solutions in <time> visiting <nodes> nodes doing <updates> updates."
+        let words: Vec<&str> = stdout.lines().last().unwrap().split(' ').collect();
+        let nodes: usize = words[6].parse().unwrap();
+        let updates: usize = words[9].parse().unwrap();
+
+        for (flag, limit) in [("-N", nodes), ("-U", updates)] {
+            let output = run(&["-E", engine, flag, &limit.to_string()], QUEENS4);
+            let stdout = String::from_utf8(output.stdout).unwrap();
+
+            assert!(output.status.success(), "{engine} {flag}: {stdout}");
+            assert!(
+                !stdout.contains("LIMIT REACHED"),
+                "{engine} {flag}: {stdout}"
+            );
+            assert!(
+                stdout.contains("Found 2 solutions"),
+                "{engine} {flag}: {stdout}"
+            );
+
+            let output = run(&["-E", engine, flag, &(limit - 1).to_string()], QUEENS4);
+            let stdout = String::from_utf8(output.stdout).unwrap();
+
+            assert!(
+                stdout.contains("LIMIT REACHED"),
+                "{engine} {flag}: {stdout}"
+            );
+        }
+    }
+}
+
+#[cfg(feature = "mems")]
+#[test]
+fn default_engine_counts_mems() {
+    let output = run(&["-F", "ndjson"], QUEENS4);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    let statistics = stdout.lines().last().unwrap();
+
+    assert!(statistics.contains("\"mems\":"), "{statistics}");
+}
+
+#[test]
+fn every_engine_takes_assumptions() {
+    let assume_file = std::env::temp_dir().join(format!("dlx-assume-{}.txt", std::process::id()));
+    std::fs::write(&assume_file, "r1 c2 a3 b-1\n").unwrap();
+
+    let expected = vec![vec![
+        "a3 b-1 c2 r1 (2 of 4)",
+        "a4 b2 c1 r3 (1 of 1)",
+        "a6 b-2 c4 r2 (1 of 1)",
+        "a7 b1 c3 r4 (1 of 1)",
+    ]];
+
+    for engine in ["dlx", "bitset", "cells"] {
+        let output = run(
+            &["-E", engine, "-a", assume_file.to_str().unwrap(), "-i", "1"],
+            QUEENS4,
+        );
+        let stdout = String::from_utf8(output.stdout).unwrap();
+
+        assert!(output.status.success(), "{engine}");
+        assert_eq!(get_solutions(&stdout), expected, "{engine}: {stdout}");
+        assert!(stdout.contains("Found 1 solution "), "{engine}: {stdout}");
+    }
+
+    std::fs::remove_file(assume_file).unwrap();
+}
+
+// Gives the options of every solution printed as text, each with its items
+// sorted followed by its position, in sorted order, since engines may start
+// an option from another item and print the options in another order.
+fn get_solutions(stdout: &str) -> Vec<Vec<String>> {
+    let mut solutions: Vec<Vec<String>> = Vec::new();
+
+    for line in stdout.lines() {
+        if line.starts_with("Solution ") {
+            solutions.push(Vec::new());
+        } else if let Some(option) = line.strip_prefix(' ')
+            && let Some(solution) = solutions.last_mut()
+        {
+            let (items, position) = option.split_at(option.find(" (").unwrap());
+
+            let mut items: Vec<&str> = items.split(' ').collect();
+            items.sort_unstable();
+
+            solution.push(format!("{}{}", items.join(" "), position));
+        }
+    }
+
+    for solution in &mut solutions {
+        solution.sort_unstable();
+    }
+
+    solutions
+}
+
+#[test]
+fn model_that_is_not_an_exact_cover_is_rejected() {
+    let model_file = std::env::temp_dir().join(format!("dlx-model-{}.txt", std::process::id()));
+    std::fs::write(&model_file, "s SATISFIABLE\nv 2 8 9 0\n").unwrap();
+
+    let output = run(&["-d", model_file.to_str().unwrap()], QUEENS4);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("Primary item \"r4\" is not covered."),
+        "{stderr}"
+    );
+
+    std::fs::remove_file(model_file).unwrap();
+}
+
+#[test]
+fn solution_file_without_solutions_is_rejected() {
+    let solution_file =
+        std::env::temp_dir().join(format!("dlx-solutions-{}.txt", std::process::id()));
+    std::fs::write(
+        &solution_file,
+        "Found 2 solutions in 1ms visiting 9 nodes.\n",
+    )
+    .unwrap();
+
+    let output = run(&["-C", solution_file.to_str().unwrap()], QUEENS4);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(!output.status.success());
+    assert!(!stdout.contains("Verified"), "{stdout}");
+
+    std::fs::remove_file(solution_file).unwrap();
+}
+
+#[test]
+fn batch_reports_a_malformed_instance_and_goes_on() {
+    let input = format!("--- first\n{QUEENS4}--- broken\nr1 | x\nr1 y\n--- last\n{QUEENS4}");
+
+    for jobs in ["1", "2"] {
+        let output = run(&["-b", "---", "-j", jobs], &input);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+
+        assert_eq!(output.status.code(), Some(1), "-j {jobs}");
+        assert!(
+            stdout.contains("Instance 2 (broken):\nError: Options must contain known items.\n"),
+            "-j {jobs}: {stdout}"
+        );
+        assert_eq!(
+            stdout.matches("Found 2 solutions").count(),
+            2,
+            "-j {jobs}: {stdout}"
+        );
+    }
+}
+
+#[test]
+fn export_of_an_item_in_no_option_is_infeasible() {
+    let output = run(&["-x", "opb"], "a b\n");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout,
+        "* #variable= 1 #constraint= 2
+* Exact cover instance exported by dlx.
+* a
+0 x1 = 1 ;
+* b
+0 x1 = 1 ;
+"
+    );
+
+    let output = run(&["-x", "lp"], "a b\n");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.status.success());
+    assert!(stdout.contains(" i1: 0 x1 = 1\n"), "{stdout}");
+    assert!(stdout.contains("Binary\n x1\nEnd\n"), "{stdout}");
+}
+
+#[test]
+fn export_without_costs_has_no_objective() {
+    let input = r#"{"primary": ["a", "b"], "options": [{"items": ["a"], "cost": 0}, ["b"]]}"#;
+
+    let output = run(&["-I", "json", "-x", "opb"], input);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.status.success());
+    assert!(!stdout.contains("min:"), "{stdout}");
+    assert!(stdout.contains("+1 x1 = 1 ;\n"), "{stdout}");
+
+    let output = run(&["-I", "json", "-x", "lp"], input);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.status.success());
+    assert!(stdout.contains("Minimize\n obj: 0 x1\n"), "{stdout}");
+
+    let input = r#"{"primary": ["a", "b"], "options": [{"items": ["a"], "cost": 3}, ["b"]]}"#;
+
+    let output = run(&["-I", "json", "-x", "opb"], input);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(stdout.contains("min: +3 x1 ;\n"), "{stdout}");
+
+    let output = run(&["-I", "json", "-x", "lp"], input);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(stdout.contains(" obj: 3 x1\n"), "{stdout}");
+}
+
+#[test]
+fn snapshot_round_trip_keeps_the_instance() {
+    let snapshot_file =
+        std::env::temp_dir().join(format!("dlx-snapshot-{}.bin", std::process::id()));
+    let snapshot_file = snapshot_file.to_str().unwrap();
+
+    let input = r#"{"primary": ["a", "b"], "secondary": ["c"], "options": [
+        {"items": ["a", "c"], "label": "first", "cost": -5},
+        {"items": ["b"], "payload": {"k": [1, 2]}, "cost": 7},
+        ["a", "b"]
+    ]}"#;
+
+    let output = run(&["-I", "json", "-S", snapshot_file, "-x", "lp"], input);
+    let exported = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.status.success());
+    assert!(exported.contains(" obj: -5 x1 + 7 x2\n"), "{exported}");
+
+    let output = run(&["-L", snapshot_file, "-x", "lp"], "");
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), exported);
+
+    let output = run(&["-L", snapshot_file, "-F", "json", "-i", "1"], "");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.status.success());
+    assert!(stdout.contains(r#""label":"first""#), "{stdout}");
+    assert!(stdout.contains(r#""payload":{"k":[1,2]}"#), "{stdout}");
+    assert!(stdout.contains(r#""solutions":2"#), "{stdout}");
+
+    std::fs::remove_file(snapshot_file).unwrap();
+}
+
+#[test]
+fn solution_files_in_every_format_are_verified() {
+    let solution_file = std::env::temp_dir().join(format!("dlx-verify-{}.txt", std::process::id()));
+    let solution_file = solution_file.to_str().unwrap();
+
+    for format in ["text", "json", "ndjson"] {
+        let output = run(&["-i", "1", "-F", format], QUEENS4);
+        let solutions = String::from_utf8(output.stdout).unwrap();
+
+        std::fs::write(solution_file, &solutions).unwrap();
+
+        let output = run(&["-C", solution_file], QUEENS4);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+
+        assert_eq!(output.status.code(), Some(0), "{format}: {stdout}");
+        assert!(
+            stdout.contains("Verified 2 solutions: 2 valid and 0 invalid."),
+            "{format}: {stdout}"
+        );
+
+        // Trading the last option of the first solution for another one
+        // leaves c3 uncovered and covers c2 twice.
+        let broken = solutions
+            .replacen(" r4 c3 a7 b1 ", " r4 c2 a6 b2 ", 1)
+            .replacen(r#"["r4","c3","a7","b1"]"#, r#"["r4","c2","a6","b2"]"#, 1);
+
+        assert_ne!(broken, solutions, "{format}");
+
+        std::fs::write(solution_file, broken).unwrap();
+
+        let output = run(&["-C", solution_file], QUEENS4);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+
+        assert_eq!(output.status.code(), Some(1), "{format}: {stdout}");
+        assert!(
+            stdout.contains("Primary item \"c2\" is covered by options 1, 4."),
+            "{format}: {stdout}"
+        );
+        assert!(
+            stdout.contains("Primary item \"c3\" is not covered."),
+            "{format}: {stdout}"
+        );
+    }
+
+    std::fs::remove_file(solution_file).unwrap();
+}
+
+#[test]
+fn batch_rejects_unique() {
+    let output = run(&["-b", "---", "-u"], &format!("---\n{QUEENS4}"));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(!output.status.success());
+    assert!(stderr.contains("cannot be combined"), "{stderr}");
+}
+
+#[test]
+fn json_output_holds_solutions_and_statistics() {
+    let output = run(&["-i", "1", "-F", "json"], QUEENS4);
+    let document: Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    assert!(output.status.success());
+
+    let solutions = document["solutions"].as_array().unwrap();
+
+    assert_eq!(solutions.len(), 2);
+    assert_eq!(solutions[0]["index"], 1);
+    assert_eq!(
+        solutions[0]["options"][0],
+        json!({"items": ["r1", "c2", "a3", "b-1"], "line": 3, "option": 2})
+    );
+    assert_eq!(document["statistics"]["solutions"], 2);
+    assert_eq!(document["statistics"]["nodes"], 17);
+}
+
+#[test]
+fn ndjson_output_has_a_record_per_line() {
+    let output = run(&["-i", "1", "-F", "ndjson"], QUEENS4);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.status.success());
+
+    let records: Vec<Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[0]["type"], "solution");
+    assert_eq!(records[1]["type"], "solution");
+    assert_eq!(records[1]["index"], 2);
+    assert_eq!(records[1]["options"].as_array().unwrap().len(), 4);
+    assert_eq!(records[2]["type"], "statistics");
+    assert_eq!(records[2]["solutions"], 2);
+}
+
+#[test]
+fn json_instance_echoes_labels_and_payloads() {
+    let input = r#"{"primary": ["a", "b"], "secondary": ["c"], "options": [
+        {"items": ["a", "c"], "label": "first", "payload": {"x": 1}},
+        ["b"],
+        {"items": ["a", "b"], "label": "both"}
+    ]}"#;
+
+    let output = run(&["-I", "json", "-i", "1"], input);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.status.success());
+    assert!(
+        stdout.starts_with(
+            "Solution 1:
+ a c (1 of 2) first {\"x\":1}
+ b (1 of 1)
+Solution 2:
+ a b (2 of 2) both
+"
+        ),
+        "{stdout}"
+    );
+
+    let output = run(&["-I", "json", "-i", "1", "-F", "json"], input);
+    let document: Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    assert_eq!(
+        document["solutions"][0]["options"][0],
+        json!({"items": ["a", "c"], "label": "first", "line": null, "option": 1, "payload": {"x": 1}})
+    );
+}
+
+#[test]
+fn matrix_rows_are_read_dense_or_sparse() {
+    let dense = "# 4 columns, the last one secondary
+4 1
+1 1 0 0
+0 0 1 1
+1 0 1 0
+0 1 0 1
+0 0 1 0
+";
+    let sparse = "4 1\n1 2\n3 4\n1 3\n2 4\n3\n";
+    let text = "c1 c2 c3 | c4\nc1 c2\nc3 c4\nc1 c3\nc2 c4\nc3\n";
+
+    let expected = run(&["-i", "1"], text);
+
+    assert!(expected.status.success());
+    assert!(String::from_utf8_lossy(&expected.stdout).contains("Found 3 solutions"));
+
+    for input in [dense, sparse] {
+        let output = run(&["-I", "matrix", "-i", "1"], input);
+
+        assert!(output.status.success());
+        assert_eq!(
+            get_solutions(&String::from_utf8(output.stdout).unwrap()),
+            get_solutions(&String::from_utf8(expected.stdout.clone()).unwrap()),
+        );
+    }
+
+    let output = run(&["-I", "matrix"], "4\n1 5\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("Row on line 2 has an invalid column index 5."),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn bitset_matches_dlx() {
+    let mut queens6 = String::from("r1 r2 r3 r4 r5 r6 c1 c2 c3 c4 c5 c6 |");
+
+    for d in 2..=12 {
+        queens6 += &format!(" a{d}");
+    }
+
+    for d in -5..=5 {
+        queens6 += &format!(" b{d}");
+    }
+
+    queens6 += "\n";
+
+    for r in 1..=6 {
+        for c in 1..=6 {
+            queens6 += &format!("r{r} c{c} a{} b{}\n", r + c, r - c);
+        }
+    }
+
+    for input in [QUEENS4, queens6.as_str()] {
+        for seed in [None, Some("7")] {
+            let mut args = vec!["-i", "1", "-F", "json"];
+            args.extend(seed.map(|seed| ["-s", seed]).into_iter().flatten());
+
+            let documents: Vec<Value> = ["dlx", "bitset"]
+                .map(|engine| {
+                    let output = run(&[&args[..], &["-E", engine]].concat(), input);
+
+                    assert!(output.status.success(), "{engine}");
+
+                    serde_json::from_slice(&output.stdout).unwrap()
+                })
+                .into();
+
+            assert_eq!(documents[0]["solutions"], documents[1]["solutions"]);
+
+            for field in ["solutions", "nodes", "updates", "max_degree", "max_level"] {
+                assert_eq!(
+                    documents[0]["statistics"][field], documents[1]["statistics"][field],
+                    "{field}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn unique_tells_the_verdict_with_its_witnesses() {
+    for engine in ["dlx", "bitset", "cells"] {
+        for (input, verdict, code, witnesses) in [
+            ("a b\na\nb\n", "unique", 0, 1),
+            ("a b\na\n", "none", 1, 0),
+            (QUEENS4, "multiple", 2, 2),
+        ] {
+            let output = run(&["-u", "-E", engine], input);
+            let stdout = String::from_utf8(output.stdout).unwrap();
+
+            assert!(
+                stdout.ends_with(&format!("Verdict: {verdict}.\n")),
+                "{engine}: {stdout}"
+            );
+            assert_eq!(get_solutions(&stdout).len(), witnesses, "{engine}");
+            assert_eq!(output.status.code(), Some(code), "{engine}");
+
+            let output = run(&["-u", "-E", engine, "-F", "json"], input);
+            let document: Value = serde_json::from_slice(&output.stdout).unwrap();
+
+            assert_eq!(document["statistics"]["verdict"], verdict, "{engine}");
+            assert_eq!(
+                document["solutions"].as_array().unwrap().len(),
+                witnesses,
+                "{engine}"
+            );
+            assert_eq!(output.status.code(), Some(code), "{engine}");
+        }
+    }
+}
+
+#[test]
+fn limit_shows_the_deepest_partial_solution() {
+    for engine in ["dlx", "bitset", "cells"] {
+        let output = run(&["-N", "5", "-E", engine], QUEENS4);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+
+        assert!(
+            stdout.contains("Partial solution, 0.25000 explored:\n"),
+            "{engine}: {stdout}"
+        );
+        assert!(
+            stdout.contains("Deepest partial solution, with 3 options:\n"),
+            "{engine}: {stdout}"
+        );
+        assert!(
+            stdout.contains("It leaves uncovered: r4 c3\n"),
+            "{engine}: {stdout}"
+        );
+
+        let output = run(&["-N", "5", "-E", engine, "-F", "json"], QUEENS4);
+        let document: Value = serde_json::from_slice(&output.stdout).unwrap();
+        let partial = &document["partial"];
+
+        assert_eq!(partial["explored"], 0.25, "{engine}");
+        assert_eq!(
+            partial["options"],
+            json!([{"items": ["r1", "c2", "a3", "b-1"], "line": 3, "option": 2}]),
+            "{engine}"
+        );
+        assert_eq!(
+            partial["deepest"]["options"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|option| option["option"].as_u64().unwrap())
+                .collect::<Vec<_>>(),
+            [1, 8, 10],
+            "{engine}"
+        );
+        assert_eq!(
+            partial["deepest"]["uncovered"],
+            json!(["r4", "c3"]),
+            "{engine}"
+        );
+    }
+}
+
+#[test]
+fn search_tree_is_written_as_dot_or_json() {
+    let tree_file = std::env::temp_dir().join(format!("dlx-tree-{}", std::process::id()));
+    let tree_file = tree_file.to_str().unwrap();
+
+    let output = run(&["-T", tree_file, "-g", "dot"], QUEENS4);
+    let dot = std::fs::read_to_string(tree_file).unwrap();
+
+    assert!(output.status.success());
+    assert!(dot.starts_with("digraph search {\n"), "{dot}");
+    assert!(dot.contains("  n0 [label=\"r1 (4)\"];\n"), "{dot}");
+    assert!(
+        dot.contains("  n0 -> n1 [label=\"r1 c1 a2 b0 (1 of 4)\"];\n"),
+        "{dot}"
+    );
+    assert_eq!(dot.matches("color=green").count(), 2, "{dot}");
+    assert_eq!(dot.matches("color=red").count(), 4, "{dot}");
+
+    // Nodes past the limit are left out and their parents drawn dashed.
+    run(&["-T", tree_file, "-n", "3"], QUEENS4);
+    let dot = std::fs::read_to_string(tree_file).unwrap();
+
+    let nodes = dot
+        .lines()
+        .filter(|line| line.contains(" [label=") && !line.contains(" -> "))
+        .count();
+
+    assert_eq!(nodes, 3, "{dot}");
+    assert_eq!(dot.matches("style=dashed").count(), 2, "{dot}");
+
+    for engine in ["dlx", "bitset", "cells"] {
+        let output = run(&["-E", engine, "-T", tree_file, "-g", "json"], QUEENS4);
+        let tree: Value =
+            serde_json::from_str(&std::fs::read_to_string(tree_file).unwrap()).unwrap();
+        let nodes = tree["nodes"].as_array().unwrap();
+
+        let stdout = String::from_utf8(output.stdout).unwrap();
+
+        // "Found 2 solutions in <time> visiting <nodes> nodes doing <updates> updates."
+        let words: Vec<&str> = stdout.lines().last().unwrap().split(' ').collect();
+
+        assert!(output.status.success(), "{engine}");
+        assert_eq!(nodes.len().to_string(), words[6], "{engine}");
+        assert_eq!(nodes[0]["parent"], Value::Null, "{engine}");
+        assert_eq!(nodes[1]["parent"], 0, "{engine}");
+        assert_eq!(nodes[1]["option"], 1, "{engine}");
+
+        let kinds: Vec<&str> = nodes
+            .iter()
+            .map(|node| node["kind"].as_str().unwrap())
+            .collect();
+
+        assert_eq!(
+            kinds.iter().filter(|&&kind| kind == "solution").count(),
+            2,
+            "{engine}"
+        );
+        assert_eq!(
+            kinds.iter().filter(|&&kind| kind == "dead_end").count(),
+            4,
+            "{engine}"
+        );
+    }
+
+    std::fs::remove_file(tree_file).unwrap();
+}
+
+#[test]
+fn profile_counts_nodes_per_level_and_item() {
+    let output = run(&["-P"], QUEENS4);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.status.success());
+    assert!(
+        stdout.ends_with(
+            "Level      Nodes  Solutions  Avg degree
+    0          1          0       4.000
+    1          4          0       1.500
+    2          6          0       0.667
+    3          4          0       0.500
+    4          2          2       0.000
+Item     Chosen  Dead ends
+r1            1          0
+r2            4          0
+r3            6          2
+r4            4          2
+"
+        ),
+        "{stdout}"
+    );
+
+    for engine in ["dlx", "bitset", "cells"] {
+        let output = run(&["-P", "-E", engine, "-F", "json"], QUEENS4);
+        let document: Value = serde_json::from_slice(&output.stdout).unwrap();
+        let statistics = &document["statistics"];
+        let profile = &statistics["profile"];
+
+        let sum = |list: &str, field: &str| -> u64 {
+            profile[list]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|entry| entry[field].as_u64().unwrap())
+                .sum()
+        };
+
+        // Every node is either a solution or has an item chosen at it.
+        assert_eq!(sum("levels", "nodes"), statistics["nodes"], "{engine}");
+        assert_eq!(sum("levels", "solutions"), 2, "{engine}");
+        assert_eq!(sum("items", "chosen") + 2, statistics["nodes"], "{engine}");
+    }
+}
+
+#[test]
+fn verbosity_levels_add_warnings_choices_and_totals() {
+    let suspicious = "a b | c d\na\na\nd\n";
+
+    let output = run(&["-V", "0"], suspicious);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(!stderr.contains("Warning"), "{stderr}");
+
+    let output = run(&["-v"], suspicious);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    for warning in [
+        "Warning: Option 2 (line 3) has the same items as option 1.\n",
+        "Warning: Option 3 (line 4) has no primary items, so it is never chosen.\n",
+        "Warning: Primary item b is in no option, so there are no solutions.\n",
+        "Warning: Secondary item c is in no option.\n",
+    ] {
+        assert!(stderr.contains(warning), "{stderr}");
+    }
+
+    assert!(
+        stdout.contains("The tree's maximum degree is 0, its depth is 0.\n"),
+        "{stdout}"
+    );
+
+    // The choices are traced on stderr, down to the level given by -c.
+    let output = run(&["-vv"], QUEENS4);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let choices: Vec<&str> = stderr
+        .lines()
+        .filter(|line| line.starts_with('L'))
+        .collect();
+
+    assert_eq!(choices.len(), 16, "{stderr}");
+    assert_eq!(choices[0], "L0: r1 c1 a2 b0 (1 of 4)");
+    assert_eq!(choices[1], "L1: r2 c3 a5 b-1 (1 of 2)");
+    assert!(!stderr.contains("Item totals"), "{stderr}");
+
+    let output = run(&["-vv", "-c", "1"], QUEENS4);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(stderr.matches("\nL0: ").count(), 4, "{stderr}");
+    assert!(!stderr.contains("\nL1: "), "{stderr}");
+
+    let output = run(&["-V", "3"], QUEENS4);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    let totals = |when: &str| {
+        stderr
+            .lines()
+            .find_map(|line| line.strip_prefix(&format!("Item totals {when}: ")))
+            .unwrap()
+            .to_string()
+    };
+
+    assert!(stderr.contains("L2: r3 has no options left\n"), "{stderr}");
+    assert!(!stderr.contains("Items left:"), "{stderr}");
+    assert!(totals("before").starts_with("r1(4) r2(4) "), "{stderr}");
+    assert_eq!(totals("before"), totals("after"));
+
+    let output = run(&["-V", "4"], QUEENS4);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(
+        stderr.contains(" Items left: r3(0) r4(1) c1(0) c3(1)\n"),
+        "{stderr}"
+    );
+}
@@ -0,0 +1,101 @@
+use dlx::config::Config;
+use dlx::input;
+use dlx::lazy::LazyDancingLinks;
+use dlx::{OptionData, Statistics};
+
+// The n-queens problem, as printed by queens.py.
+fn queens(n: usize) -> String {
+    let mut text = String::new();
+
+    for i in 1..=n {
+        text += &format!("r{i} ");
+    }
+
+    for i in 1..=n {
+        text += &format!("c{i} ");
+    }
+
+    text += "|";
+
+    for i in 2..=2 * n {
+        text += &format!(" a{i}");
+    }
+
+    for i in 1..2 * n {
+        text += &format!(" b{}", i as isize - n as isize);
+    }
+
+    text += "\n";
+
+    for i in 1..=n {
+        for j in 1..=n {
+            text += &format!("r{i} c{j} a{} b{}\n", i + j, i as isize - j as isize);
+        }
+    }
+
+    text
+}
+
+// Solves the instance with `DancingLinks` and with `LazyDancingLinks`, whose
+// generator returns the options of the same instance, and gives the labels of
+// the options the latter generated.
+fn solve_both(text: &str) -> (Statistics, Statistics, Vec<Option<String>>) {
+    let config = Config::build(&[String::from("dlx")]).unwrap();
+
+    let mut dlx = input::read_text(&config, text.as_bytes());
+
+    let options = dlx.get_option_items();
+    let names: Vec<&str> = (1..=dlx.get_item_count())
+        .map(|i| dlx.get_item_name(i))
+        .collect();
+    let (primary, secondary) = names.split_at(dlx.get_primary());
+
+    let mut ldlx = LazyDancingLinks::new(&config, primary, secondary, |i| {
+        options
+            .iter()
+            .enumerate()
+            .filter(|(_, items)| items.contains(&i))
+            .map(|(k, items)| {
+                let data = OptionData {
+                    label: Some(format!("o{}", k + 1)),
+                    ..OptionData::default()
+                };
+
+                (items.clone(), data)
+            })
+            .collect()
+    });
+
+    let lazy_stats = ldlx.dance();
+
+    let labels = (1..=ldlx.get_option_count())
+        .map(|k| ldlx.get_option_data(k).label.clone())
+        .collect();
+
+    (dlx.dance(), lazy_stats, labels)
+}
+
+#[test]
+fn lazy_counts_the_solutions_of_queens() {
+    for (n, solutions) in [(4, 2), (6, 4), (8, 92)] {
+        let (stats, lazy_stats, _) = solve_both(&queens(n));
+
+        assert_eq!(stats.solution_count, solutions, "{n} queens");
+        assert_eq!(lazy_stats.solution_count, solutions, "{n} queens");
+    }
+}
+
+#[test]
+fn lazy_keeps_repeated_options_apart() {
+    let (stats, lazy_stats, mut labels) = solve_both("a b c | d\na b\nc d\na b\nc\nb c\na\n");
+
+    assert_eq!(stats.solution_count, 5);
+    assert_eq!(lazy_stats.solution_count, 5);
+
+    // Every option is generated, the repeated one twice with its own data.
+    labels.sort();
+
+    let expected: Vec<Option<String>> = (1..=6).map(|k| Some(format!("o{k}"))).collect();
+
+    assert_eq!(labels, expected);
+}
@@ -0,0 +1,145 @@
+use dlx::DancingLinks;
+use dlx::bitset::BitsetDancer;
+use dlx::cells::DancingCells;
+use dlx::config::Config;
+use dlx::input;
+
+// The n-queens problem, as printed by queens.py.
+fn queens(config: &Config, n: usize) -> DancingLinks {
+    let mut text = String::new();
+
+    for i in 1..=n {
+        text += &format!("r{i} ");
+    }
+
+    for i in 1..=n {
+        text += &format!("c{i} ");
+    }
+
+    text += "|";
+
+    for i in 2..=2 * n {
+        text += &format!(" a{i}");
+    }
+
+    for i in 1..2 * n {
+        text += &format!(" b{}", i as isize - n as isize);
+    }
+
+    text += "\n";
+
+    for i in 1..=n {
+        for j in 1..=n {
+            text += &format!("r{i} c{j} a{} b{}\n", i + j, i as isize - j as isize);
+        }
+    }
+
+    input::read_text(config, text.as_bytes())
+}
+
+fn default_config() -> Config {
+    Config::build(&[String::from("dlx")]).unwrap()
+}
+
+#[test]
+fn dance_restores_the_links() {
+    let mut dlx = queens(&default_config(), 8);
+
+    for _ in 0..2 {
+        let stats = dlx.dance();
+
+        assert_eq!(stats.solution_count, 92);
+        assert_eq!(stats.visited_nodes, 1199);
+        assert_eq!(stats.update_count, 21375);
+        assert_eq!(dlx.check_links(), Ok(()));
+    }
+}
+
+#[test]
+fn timeout_is_noticed_between_clock_checks() {
+    let config = Config::build(&["dlx", "-t", "0"].map(String::from)).unwrap();
+    let mut dlx = queens(&config, 8);
+
+    let (stats, _) = dlx::output::capture(|| dlx.dance());
+
+    assert!(stats.timed_out);
+    assert!(stats.visited_nodes < 1199);
+    assert_eq!(dlx.check_links(), Ok(()));
+}
+
+// Counts the solutions with every engine, which must agree.
+fn count_solutions(dlx: &mut DancingLinks) -> usize {
+    let cells = DancingCells::from_links(dlx).dance().solution_count;
+    let bitset = BitsetDancer::new(dlx).unwrap().dance().solution_count;
+    let links = dlx.dance().solution_count;
+
+    assert_eq!(cells, links);
+    assert_eq!(bitset, links);
+
+    links
+}
+
+#[test]
+fn options_and_items_are_deactivated_and_appended() {
+    let config = default_config();
+    let mut dlx = DancingLinks::new(&config, &["a", "b"], &["x"]);
+
+    assert_eq!(dlx.add_option("a"), 1);
+    assert_eq!(dlx.add_option("b"), 2);
+    assert_eq!(dlx.add_option("a b x"), 3);
+    assert_eq!(count_solutions(&mut dlx), 2);
+
+    dlx.deactivate_option(3);
+    assert!(!dlx.is_option_active(3));
+    assert_eq!(count_solutions(&mut dlx), 1);
+
+    dlx.deactivate_option(1);
+    assert_eq!(count_solutions(&mut dlx), 0);
+
+    dlx.reactivate_option(3);
+    dlx.reactivate_option(1);
+    assert_eq!(count_solutions(&mut dlx), 2);
+
+    // Without b, neither of its options is left and only a is to be covered.
+    let b = dlx.find_item("b").unwrap();
+    dlx.deactivate_item(b);
+    assert_eq!(count_solutions(&mut dlx), 1);
+
+    dlx.reactivate_item(b);
+    assert_eq!(count_solutions(&mut dlx), 2);
+
+    let c = dlx.append_item("c", true);
+    assert_eq!(dlx.get_item_name(c), "c");
+    assert_eq!(dlx.find_item("x"), Some(4));
+    assert_eq!(count_solutions(&mut dlx), 0);
+
+    assert_eq!(dlx.add_option("c x"), 4);
+    assert_eq!(dlx.get_option_items()[2], [1, 2, 4]);
+    assert_eq!(count_solutions(&mut dlx), 1);
+    assert_eq!(dlx.check_links(), Ok(()));
+}
+
+#[test]
+fn link_checks_pass_on_every_engine() {
+    let config = Config::build(&["dlx", "-k"].map(String::from)).unwrap();
+    let mut dlx = queens(&config, 6);
+
+    assert_eq!(dlx.check_links(), Ok(()));
+
+    // With -k a violation at any node of the search would panic.
+    assert_eq!(dlx.dance().solution_count, 4);
+    assert_eq!(dlx.check_links(), Ok(()));
+
+    dlx.deactivate_option(2);
+    dlx.deactivate_item(dlx.find_item("a7").unwrap());
+
+    assert_eq!(dlx.check_links(), Ok(()));
+
+    let solutions = dlx.dance().solution_count;
+    let mut dc = DancingCells::from_links(&dlx);
+
+    assert_eq!(dc.check_links(), Ok(()));
+    assert_eq!(dc.dance().solution_count, solutions);
+    assert_eq!(dc.check_links(), Ok(()));
+    assert_eq!(dlx.check_links(), Ok(()));
+}